@@ -0,0 +1,309 @@
+//! Sanity checks on the geometry of body plans.
+
+use crate::{collision::segments_intersect, config::BodyConfig, creature::Segment};
+
+/// The first problem found with a body plan. Segments are identified by their index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BodyPlanError {
+    Empty,
+    TooManySegments {
+        count: usize,
+        max: usize,
+    },
+    /// A segment with a coordinate that is NaN or infinite.
+    NonFinite {
+        segment: usize,
+    },
+    /// A segment shorter than the minimum length, which has no usable direction.
+    Degenerate {
+        segment: usize,
+        length: f32,
+    },
+    /// A point further from the creature's origin than the maximum radius.
+    TooLarge {
+        segment: usize,
+        radius: f32,
+    },
+    /// The segments form more than one separate piece.
+    Disconnected {
+        pieces: usize,
+    },
+    /// Two segments cross or touch somewhere other than a shared endpoint.
+    SelfIntersection {
+        segment_a: usize,
+        segment_b: usize,
+    },
+}
+
+impl std::fmt::Display for BodyPlanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            BodyPlanError::Empty => write!(f, "body has no segments"),
+            BodyPlanError::TooManySegments { count, max } => {
+                write!(
+                    f,
+                    "body has {} segments, more than the limit of {}",
+                    count, max
+                )
+            }
+            BodyPlanError::NonFinite { segment } => {
+                write!(f, "segment {} has a coordinate that is not finite", segment)
+            }
+            BodyPlanError::Degenerate { segment, length } => {
+                write!(f, "segment {} is too short ({})", segment, length)
+            }
+            BodyPlanError::TooLarge { segment, radius } => write!(
+                f,
+                "segment {} reaches {} from the origin, further than the limit",
+                segment, radius
+            ),
+            BodyPlanError::Disconnected { pieces } => {
+                write!(f, "body is in {} disconnected pieces", pieces)
+            }
+            BodyPlanError::SelfIntersection {
+                segment_a,
+                segment_b,
+            } => write!(f, "segments {} and {} intersect", segment_a, segment_b),
+        }
+    }
+}
+
+impl std::error::Error for BodyPlanError {}
+
+/// Checks a body plan against the limits in `config`, returning the first problem found.
+pub fn validate_body_plan(segments: &[Segment], config: &BodyConfig) -> Result<(), BodyPlanError> {
+    if segments.is_empty() {
+        return Err(BodyPlanError::Empty);
+    }
+    if segments.len() > config.max_segments {
+        return Err(BodyPlanError::TooManySegments {
+            count: segments.len(),
+            max: config.max_segments,
+        });
+    }
+    for (i, segment) in segments.iter().enumerate() {
+        if !(segment.a.is_finite() && segment.b.is_finite()) {
+            return Err(BodyPlanError::NonFinite { segment: i });
+        }
+        if segment.length() < config.min_segment_length {
+            return Err(BodyPlanError::Degenerate {
+                segment: i,
+                length: segment.length(),
+            });
+        }
+        let radius = segment.max_dist_from_origin_squared().sqrt();
+        if radius > config.max_radius {
+            return Err(BodyPlanError::TooLarge { segment: i, radius });
+        }
+    }
+    let pieces = count_pieces(segments);
+    if pieces > 1 {
+        return Err(BodyPlanError::Disconnected { pieces });
+    }
+    for i in 0..segments.len() {
+        for j in i + 1..segments.len() {
+            let (a, b) = (&segments[i], &segments[j]);
+            if segments_clash(a.a, a.b, b.a, b.b) {
+                return Err(BodyPlanError::SelfIntersection {
+                    segment_a: i,
+                    segment_b: j,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether two segments of a body touch somewhere other than a shared endpoint. Segments that share
+/// an endpoint clash only if they are the same segment, or run along each other from that point.
+pub fn segments_clash(a0: glam::Vec2, a1: glam::Vec2, b0: glam::Vec2, b1: glam::Vec2) -> bool {
+    if (a0 == b0 && a1 == b1) || (a0 == b1 && a1 == b0) {
+        return true;
+    }
+    for (shared, a_end) in [(a0, a1), (a1, a0)] {
+        for (other, b_end) in [(b0, b1), (b1, b0)] {
+            if shared == other {
+                let (a, b) = (a_end - shared, b_end - shared);
+                // Two segments leaving the same point in different directions only meet there.
+                let collinear = a.perp_dot(b).abs() <= f32::EPSILON * a.length() * b.length();
+                return collinear && a.dot(b) > 0.0;
+            }
+        }
+    }
+    segments_intersect(a0, a1, b0, b1)
+}
+
+/// Number of groups of segments connected through shared endpoints.
+pub(crate) fn count_pieces(segments: &[Segment]) -> usize {
+    // Union-find over segment indices.
+    let mut parents: Vec<usize> = (0..segments.len()).collect();
+    fn root(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+    for i in 0..segments.len() {
+        for j in i + 1..segments.len() {
+            let (a, b) = (&segments[i], &segments[j]);
+            if a.a == b.a || a.a == b.b || a.b == b.a || a.b == b.b {
+                let (root_i, root_j) = (root(&mut parents, i), root(&mut parents, j));
+                parents[root_i] = root_j;
+            }
+        }
+    }
+    (0..segments.len())
+        .filter(|&i| root(&mut parents, i) == i)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        creature::{tests, SegmentType},
+        genome::Genome,
+    };
+
+    /// An Energy segment; validation doesn't look at segment types.
+    fn segment(a: (f32, f32), b: (f32, f32)) -> Segment {
+        tests::segment(a, b, SegmentType::Energy)
+    }
+
+    fn validate(segments: &[Segment]) -> Result<(), BodyPlanError> {
+        validate_body_plan(segments, &BodyConfig::default())
+    }
+
+    fn self_intersection(segment_a: usize, segment_b: usize) -> Result<(), BodyPlanError> {
+        Err(BodyPlanError::SelfIntersection {
+            segment_a,
+            segment_b,
+        })
+    }
+
+    #[test]
+    fn default_genome_is_valid() {
+        assert_eq!(Genome::default().validate(&BodyConfig::default()), Ok(()));
+    }
+
+    #[test]
+    fn segments_may_meet_at_shared_endpoints() {
+        let v = [
+            segment((0.0, 0.0), (10.0, 20.0)),
+            segment((0.0, 0.0), (-10.0, 20.0)),
+        ];
+        assert_eq!(validate(&v), Ok(()));
+        let triangle = [
+            segment((0.0, 0.0), (20.0, 0.0)),
+            segment((20.0, 0.0), (0.0, 20.0)),
+            segment((0.0, 20.0), (0.0, 0.0)),
+        ];
+        assert_eq!(validate(&triangle), Ok(()));
+        let straight = [
+            segment((0.0, 0.0), (0.0, 10.0)),
+            segment((0.0, 10.0), (0.0, 30.0)),
+        ];
+        assert_eq!(validate(&straight), Ok(()));
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(validate(&[]), Err(BodyPlanError::Empty));
+    }
+
+    #[test]
+    fn too_many_segments() {
+        let chain: Vec<Segment> = (0..33)
+            .map(|i| segment((0.0, i as f32 * 2.0), (0.0, (i + 1) as f32 * 2.0)))
+            .collect();
+        assert_eq!(
+            validate(&chain),
+            Err(BodyPlanError::TooManySegments { count: 33, max: 32 })
+        );
+    }
+
+    #[test]
+    fn non_finite() {
+        let segments = [
+            segment((0.0, 0.0), (0.0, 10.0)),
+            segment((0.0, 10.0), (f32::NAN, 0.0)),
+        ];
+        assert_eq!(
+            validate(&segments),
+            Err(BodyPlanError::NonFinite { segment: 1 })
+        );
+    }
+
+    #[test]
+    fn degenerate() {
+        assert_eq!(
+            validate(&[segment((0.0, 0.0), (0.0, 0.5))]),
+            Err(BodyPlanError::Degenerate {
+                segment: 0,
+                length: 0.5
+            })
+        );
+    }
+
+    #[test]
+    fn too_large() {
+        assert_eq!(
+            validate(&[segment((0.0, 0.0), (0.0, 200.0))]),
+            Err(BodyPlanError::TooLarge {
+                segment: 0,
+                radius: 200.0
+            })
+        );
+    }
+
+    #[test]
+    fn disconnected() {
+        let segments = [
+            segment((0.0, 0.0), (0.0, 10.0)),
+            segment((5.0, 0.0), (5.0, 10.0)),
+        ];
+        assert_eq!(
+            validate(&segments),
+            Err(BodyPlanError::Disconnected { pieces: 2 })
+        );
+    }
+
+    #[test]
+    fn crossing_segments_intersect() {
+        let z = [
+            segment((0.0, 0.0), (20.0, 20.0)),
+            segment((20.0, 20.0), (20.0, 0.0)),
+            segment((20.0, 0.0), (0.0, 20.0)),
+        ];
+        assert_eq!(validate(&z), self_intersection(0, 2));
+    }
+
+    #[test]
+    fn folding_back_along_a_segment_intersects() {
+        let fold = [
+            segment((0.0, 0.0), (0.0, 30.0)),
+            segment((0.0, 30.0), (0.0, 10.0)),
+        ];
+        assert_eq!(validate(&fold), self_intersection(0, 1));
+        let diagonal = [
+            segment((0.0, 0.0), (30.0, 30.0)),
+            segment((0.0, 0.0), (10.0, 10.0)),
+        ];
+        assert_eq!(validate(&diagonal), self_intersection(0, 1));
+    }
+
+    #[test]
+    fn repeated_segments_intersect() {
+        let repeated = [
+            segment((0.0, 0.0), (0.0, 30.0)),
+            segment((0.0, 0.0), (0.0, 30.0)),
+        ];
+        assert_eq!(validate(&repeated), self_intersection(0, 1));
+        let reversed = [
+            segment((0.0, 0.0), (0.0, 30.0)),
+            segment((0.0, 30.0), (0.0, 0.0)),
+        ];
+        assert_eq!(validate(&reversed), self_intersection(0, 1));
+    }
+}
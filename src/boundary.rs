@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+
+/// What happens at the edges of the world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Boundary {
+    /// Creatures bounce off the edges of the world.
+    #[default]
+    Walls,
+    /// The world wraps around on both axes, so there are no edges.
+    Toroidal,
+}
+
+impl Boundary {
+    /// Brings a point that has left the world back in on the opposite side. With walls, points are
+    /// left alone.
+    pub fn wrap(self, point: glam::Vec2, world_size: glam::Vec2) -> glam::Vec2 {
+        match self {
+            Boundary::Walls => point,
+            Boundary::Toroidal => (point + world_size).rem_euclid(world_size * 2.0) - world_size,
+        }
+    }
+
+    /// The shortest vector from `from` to `to`. In a toroidal world this may cross an edge.
+    pub fn offset(self, from: glam::Vec2, to: glam::Vec2, world_size: glam::Vec2) -> glam::Vec2 {
+        let offset = to - from;
+        match self {
+            Boundary::Walls => offset,
+            Boundary::Toroidal => {
+                let extent = world_size * 2.0;
+                offset - (offset / extent).round() * extent
+            }
+        }
+    }
+
+    /// Offsets at which something with the given bounding circle appears in the world. Something
+    /// straddling the edge of a toroidal world appears on both sides.
+    pub fn copies(
+        self,
+        center: glam::Vec2,
+        radius: f32,
+        world_size: glam::Vec2,
+    ) -> Vec<glam::Vec2> {
+        let extent = world_size * 2.0;
+        let axis = |center: f32, size: f32, extent: f32| -> Vec<f32> {
+            let mut offsets = vec![0.0];
+            if self == Boundary::Toroidal {
+                if center + radius > size {
+                    offsets.push(-extent);
+                }
+                if center - radius < -size {
+                    offsets.push(extent);
+                }
+            }
+            offsets
+        };
+        let mut copies = Vec::with_capacity(1);
+        for y in axis(center.y, world_size.y, extent.y) {
+            for x in axis(center.x, world_size.x, extent.x) {
+                copies.push(glam::Vec2::new(x, y));
+            }
+        }
+        copies
+    }
+
+    pub fn distance_squared(self, a: glam::Vec2, b: glam::Vec2, world_size: glam::Vec2) -> f32 {
+        self.offset(a, b, world_size).length_squared()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORLD_SIZE: glam::Vec2 = glam::Vec2::new(100.0, 50.0);
+
+    #[test]
+    fn walls_leave_points_alone() {
+        let point = glam::Vec2::new(130.0, -70.0);
+        assert_eq!(Boundary::Walls.wrap(point, WORLD_SIZE), point);
+        assert_eq!(
+            Boundary::Walls.offset(
+                glam::Vec2::new(90.0, 0.0),
+                glam::Vec2::new(-90.0, 0.0),
+                WORLD_SIZE
+            ),
+            glam::Vec2::new(-180.0, 0.0)
+        );
+        assert_eq!(
+            Boundary::Walls.copies(glam::Vec2::new(95.0, 45.0), 10.0, WORLD_SIZE),
+            vec![glam::Vec2::ZERO]
+        );
+    }
+
+    #[test]
+    fn wrap_brings_points_back_in_on_the_opposite_side() {
+        let wrap = |x, y| Boundary::Toroidal.wrap(glam::Vec2::new(x, y), WORLD_SIZE);
+        assert_eq!(wrap(10.0, -20.0), glam::Vec2::new(10.0, -20.0));
+        assert_eq!(wrap(110.0, 0.0), glam::Vec2::new(-90.0, 0.0));
+        assert_eq!(wrap(-110.0, 0.0), glam::Vec2::new(90.0, 0.0));
+        assert_eq!(wrap(0.0, 60.0), glam::Vec2::new(0.0, -40.0));
+        // Points more than a whole world away come back too.
+        assert_eq!(wrap(330.0, -160.0), glam::Vec2::new(-70.0, 40.0));
+    }
+
+    #[test]
+    fn offset_takes_the_shortest_way_around() {
+        let offset = |from: (f32, f32), to: (f32, f32)| {
+            Boundary::Toroidal.offset(from.into(), to.into(), WORLD_SIZE)
+        };
+        assert_eq!(offset((10.0, 0.0), (30.0, 0.0)), glam::Vec2::new(20.0, 0.0));
+        assert_eq!(
+            offset((90.0, 0.0), (-90.0, 0.0)),
+            glam::Vec2::new(20.0, 0.0)
+        );
+        assert_eq!(
+            offset((-90.0, 0.0), (90.0, 0.0)),
+            glam::Vec2::new(-20.0, 0.0)
+        );
+        assert_eq!(
+            offset((0.0, 45.0), (0.0, -45.0)),
+            glam::Vec2::new(0.0, 10.0)
+        );
+        assert_eq!(
+            Boundary::Toroidal.distance_squared(
+                glam::Vec2::new(95.0, 45.0),
+                glam::Vec2::new(-95.0, -45.0),
+                WORLD_SIZE
+            ),
+            200.0
+        );
+    }
+
+    #[test]
+    fn copies_appear_across_every_edge_crossed() {
+        let copies = |x, y| Boundary::Toroidal.copies(glam::Vec2::new(x, y), 10.0, WORLD_SIZE);
+        assert_eq!(copies(0.0, 0.0), vec![glam::Vec2::ZERO]);
+        assert_eq!(
+            copies(95.0, 0.0),
+            vec![glam::Vec2::ZERO, glam::Vec2::new(-200.0, 0.0)]
+        );
+        assert_eq!(
+            copies(0.0, -45.0),
+            vec![glam::Vec2::ZERO, glam::Vec2::new(0.0, 100.0)]
+        );
+        // Near a corner, it shows up in all four.
+        assert_eq!(
+            copies(-95.0, 45.0),
+            vec![
+                glam::Vec2::ZERO,
+                glam::Vec2::new(200.0, 0.0),
+                glam::Vec2::new(0.0, -100.0),
+                glam::Vec2::new(200.0, -100.0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{
+    creature::{Creature, SegmentType},
+    spatial::SpatialGrid,
+};
+use rayon::prelude::*;
+
+/// A segment of one creature touching a segment of another creature.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    /// Index into the creatures the contact was found in.
+    pub creature_a: usize,
+    /// Index into the segments of `creature_a`.
+    pub segment_a: usize,
+    pub type_a: SegmentType,
+    pub creature_b: usize,
+    pub segment_b: usize,
+    pub type_b: SegmentType,
+}
+
+impl Contact {
+    /// The same contact with creatures a and b the other way round.
+    pub fn swapped(self) -> Self {
+        Self {
+            creature_a: self.creature_b,
+            segment_a: self.segment_b,
+            type_a: self.type_b,
+            creature_b: self.creature_a,
+            segment_b: self.segment_a,
+            type_b: self.type_a,
+        }
+    }
+}
+
+/// Which side of the line through `a` and `b` the point `c` is on. Zero if collinear.
+fn orientation(a: glam::Vec2, b: glam::Vec2, c: glam::Vec2) -> f32 {
+    (b - a).perp_dot(c - a)
+}
+
+/// Assuming `c` is collinear with `a` and `b`, whether `c` lies between them.
+fn on_segment(a: glam::Vec2, b: glam::Vec2, c: glam::Vec2) -> bool {
+    c.cmpge(a.min(b)).all() && c.cmple(a.max(b)).all()
+}
+
+/// Whether the line segment from `a0` to `a1` intersects the line segment from `b0` to `b1`.
+/// Touching endpoints and overlapping collinear segments count as intersecting.
+pub fn segments_intersect(a0: glam::Vec2, a1: glam::Vec2, b0: glam::Vec2, b1: glam::Vec2) -> bool {
+    let o1 = orientation(a0, a1, b0);
+    let o2 = orientation(a0, a1, b1);
+    let o3 = orientation(b0, b1, a0);
+    let o4 = orientation(b0, b1, a1);
+    if ((o1 > 0.0 && o2 < 0.0) || (o1 < 0.0 && o2 > 0.0))
+        && ((o3 > 0.0 && o4 < 0.0) || (o3 < 0.0 && o4 > 0.0))
+    {
+        return true;
+    }
+    (o1 == 0.0 && on_segment(a0, a1, b0))
+        || (o2 == 0.0 && on_segment(a0, a1, b1))
+        || (o3 == 0.0 && on_segment(b0, b1, a0))
+        || (o4 == 0.0 && on_segment(b0, b1, a1))
+}
+
+/// What to add to things at `to` to bring them to the copy nearest `from`. Always zero unless the
+/// world wraps around.
+pub fn wrap_shift(grid: &SpatialGrid, from: glam::Vec2, to: glam::Vec2) -> glam::Vec2 {
+    grid.boundary().offset(from, to, grid.world_size()) - (to - from)
+}
+
+/// Every pair of touching segments between the two creatures. Does not check that the creatures
+/// are close enough to touch first. `shift` is added to creature b's position, to bring it next to
+/// creature a when they touch across the edge of a toroidal world.
+pub fn creature_contacts(
+    creatures: &[Creature],
+    creature_a: usize,
+    creature_b: usize,
+    shift: glam::Vec2,
+    contacts: &mut Vec<Contact>,
+) {
+    let a = &creatures[creature_a];
+    let b = &creatures[creature_b];
+    for (segment_a, sa) in a.segments.iter().enumerate() {
+        let (a0, a1) = a.world_segment(sa);
+        for (segment_b, sb) in b.segments.iter().enumerate() {
+            let (b0, b1) = b.world_segment(sb);
+            if segments_intersect(a0, a1, b0 + shift, b1 + shift) {
+                contacts.push(Contact {
+                    creature_a,
+                    segment_a,
+                    type_a: sa.t,
+                    creature_b,
+                    segment_b,
+                    type_b: sb.t,
+                });
+            }
+        }
+    }
+}
+
+/// Every pair of touching segments between different creatures. Each pair is reported once, with
+/// `creature_a` the creature with the lower ID. Contacts are sorted by creature IDs and then
+/// segments (see `indices_by_id`). The grid must have been built from the same creatures.
+pub fn find_contacts(creatures: &[Creature], grid: &SpatialGrid) -> Vec<Contact> {
+    // Each creature's contacts are found in parallel, then put in creature ID order.
+    let mut contacts: Vec<Contact> = (0..creatures.len())
+        .into_par_iter()
+        .flat_map_iter(|creature_a| {
+            let mut contacts = Vec::new();
+            let (center, radius) = grid.bounds(creature_a);
+            for creature_b in grid.query_circle(center, radius) {
+                if creature_b > creature_a {
+                    let shift = wrap_shift(grid, center, creatures[creature_b].position);
+                    creature_contacts(creatures, creature_a, creature_b, shift, &mut contacts);
+                }
+            }
+            contacts
+        })
+        .map(|contact| {
+            if creatures[contact.creature_a].id < creatures[contact.creature_b].id {
+                contact
+            } else {
+                contact.swapped()
+            }
+        })
+        .collect();
+    contacts.sort_unstable_by_key(|contact| {
+        (
+            creatures[contact.creature_a].id,
+            creatures[contact.creature_b].id,
+            contact.segment_a,
+            contact.segment_b,
+        )
+    });
+    contacts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        boundary::Boundary,
+        creature::tests::{creature, segment},
+    };
+
+    fn intersect(a0: (f32, f32), a1: (f32, f32), b0: (f32, f32), b1: (f32, f32)) -> bool {
+        let v = |(x, y)| glam::Vec2::new(x, y);
+        let result = segments_intersect(v(a0), v(a1), v(b0), v(b1));
+        // The answer doesn't depend on the order of the segments or their endpoints.
+        assert_eq!(segments_intersect(v(b1), v(b0), v(a0), v(a1)), result);
+        result
+    }
+
+    #[test]
+    fn crossing_segments_intersect() {
+        assert!(intersect(
+            (0.0, 0.0),
+            (10.0, 10.0),
+            (0.0, 10.0),
+            (10.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn separate_segments_do_not_intersect() {
+        assert!(!intersect((0.0, 0.0), (10.0, 0.0), (0.0, 1.0), (10.0, 1.0)));
+        assert!(!intersect(
+            (0.0, 0.0),
+            (10.0, 10.0),
+            (6.0, 4.0),
+            (20.0, -10.0)
+        ));
+        // Would cross if both were a little longer.
+        assert!(!intersect((0.0, 0.0), (4.0, 4.0), (0.0, 10.0), (4.9, 5.1)));
+    }
+
+    #[test]
+    fn touching_segments_intersect() {
+        assert!(intersect(
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0)
+        ));
+        assert!(intersect((0.0, 0.0), (10.0, 0.0), (5.0, 0.0), (5.0, 10.0)));
+    }
+
+    #[test]
+    fn collinear_segments_intersect_only_if_they_overlap() {
+        assert!(intersect((0.0, 0.0), (10.0, 0.0), (5.0, 0.0), (15.0, 0.0)));
+        assert!(intersect((0.0, 0.0), (10.0, 0.0), (2.0, 0.0), (8.0, 0.0)));
+        assert!(!intersect(
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (11.0, 0.0),
+            (15.0, 0.0)
+        ));
+    }
+
+    fn contacts(creatures: &[Creature], boundary: Boundary) -> Vec<Contact> {
+        let grid = SpatialGrid::build(glam::Vec2::new(100.0, 100.0), boundary, creatures);
+        find_contacts(creatures, &grid)
+    }
+
+    #[test]
+    fn contacts_between_creatures_are_reported_once() {
+        let horizontal = segment((-10.0, 0.0), (10.0, 0.0), SegmentType::Attack);
+        let vertical = segment((0.0, -10.0), (0.0, 10.0), SegmentType::Defend);
+        let creatures = [
+            creature(1, glam::Vec2::ZERO, vec![horizontal]),
+            creature(2, glam::Vec2::new(50.0, 50.0), vec![horizontal]),
+            creature(3, glam::Vec2::new(5.0, 0.0), vec![vertical]),
+        ];
+        assert_eq!(
+            contacts(&creatures, Boundary::Walls),
+            vec![Contact {
+                creature_a: 0,
+                segment_a: 0,
+                type_a: SegmentType::Attack,
+                creature_b: 2,
+                segment_b: 0,
+                type_b: SegmentType::Defend,
+            }]
+        );
+    }
+
+    #[test]
+    fn contacts_are_in_creature_id_order() {
+        let horizontal = segment((-10.0, 0.0), (10.0, 0.0), SegmentType::Attack);
+        let vertical = segment((0.0, -10.0), (0.0, 10.0), SegmentType::Defend);
+        let creatures = [
+            creature(3, glam::Vec2::new(5.0, 0.0), vec![vertical]),
+            creature(1, glam::Vec2::ZERO, vec![horizontal]),
+            creature(2, glam::Vec2::new(-5.0, 0.0), vec![vertical]),
+        ];
+        let found: Vec<_> = contacts(&creatures, Boundary::Walls)
+            .iter()
+            .map(|contact| {
+                (
+                    creatures[contact.creature_a].id,
+                    contact.type_a,
+                    creatures[contact.creature_b].id,
+                    contact.type_b,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (1, SegmentType::Attack, 2, SegmentType::Defend),
+                (1, SegmentType::Attack, 3, SegmentType::Defend),
+            ]
+        );
+    }
+
+    #[test]
+    fn contacts_wrap_around_toroidal_edges() {
+        let horizontal = segment((-10.0, 0.0), (10.0, 0.0), SegmentType::Energy);
+        // The first creature sticks out of the right edge, and the second out of the left, so
+        // they overlap across it.
+        let creatures = [
+            creature(1, glam::Vec2::new(95.0, 0.0), vec![horizontal]),
+            creature(2, glam::Vec2::new(-95.0, 0.0), vec![horizontal]),
+        ];
+        assert!(contacts(&creatures, Boundary::Walls).is_empty());
+        assert_eq!(contacts(&creatures, Boundary::Toroidal).len(), 1);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{
+    collision::Contact,
+    config::Config,
+    creature::{Creature, DeathCause, SegmentType},
+};
+use std::collections::BTreeMap;
+
+/// Drains energy from creatures touched by another creature's Attack segments, and gives it to
+/// the attacker. Creatures drained of all their energy die.
+///
+/// Each Attack segment drains each creature it touches once, however many of that creature's
+/// segments it touches, and less if any of them is a Defend segment. Drains are applied in order of
+/// attacker ID, attack segment and victim ID.
+pub fn resolve_combat(
+    creatures: &mut [Creature],
+    contacts: &[Contact],
+    config: &Config,
+    delta_time: f32,
+) {
+    // Keyed by (attacker ID, attack segment, victim ID). The values are (attacker index, victim
+    // index, whether a Defend segment was touched).
+    let mut attacks: BTreeMap<(u64, usize, u64), (usize, usize, bool)> = BTreeMap::new();
+    for contact in contacts.iter() {
+        for contact in [*contact, contact.swapped()] {
+            if contact.type_a != SegmentType::Attack {
+                continue;
+            }
+            let key = (
+                creatures[contact.creature_a].id,
+                contact.segment_a,
+                creatures[contact.creature_b].id,
+            );
+            let attack =
+                attacks
+                    .entry(key)
+                    .or_insert((contact.creature_a, contact.creature_b, false));
+            attack.2 |= contact.type_b == SegmentType::Defend;
+        }
+    }
+    for ((_, attack_segment, _), (attacker, victim, defended)) in attacks {
+        attack(
+            creatures,
+            (attacker, attack_segment),
+            victim,
+            defended,
+            config,
+            delta_time,
+        );
+    }
+}
+
+/// `attacker` is a (creature index, segment index) pair.
+fn attack(
+    creatures: &mut [Creature],
+    (attacker, attack_segment): (usize, usize),
+    victim: usize,
+    defended: bool,
+    config: &Config,
+    delta_time: f32,
+) {
+    if creatures[attacker].dead || creatures[victim].dead {
+        return;
+    }
+    let mut drain = creatures[attacker].segments[attack_segment].length()
+        * config.combat.attack_drain_rate
+        * delta_time;
+    if defended {
+        drain *= 1.0 - config.combat.defend_block_fraction;
+    }
+    let victim = &mut creatures[victim];
+    let drain = drain.min(victim.energy);
+    victim.energy -= drain;
+    if victim.energy <= 0.0 {
+        victim.kill(DeathCause::Attack);
+    }
+    let attacker = &mut creatures[attacker];
+    attacker.energy = (attacker.energy + drain).min(attacker.energy_capacity(&config.creature));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        boundary::Boundary,
+        collision::find_contacts,
+        creature::tests::{creature, segment},
+        spatial::SpatialGrid,
+    };
+
+    /// A 20 long Attack segment, with a capacity of 200 energy.
+    fn attacker(energy: f32) -> Creature {
+        let mut attacker = creature(
+            1,
+            glam::Vec2::ZERO,
+            vec![segment((-10.0, 0.0), (10.0, 0.0), SegmentType::Attack)],
+        );
+        attacker.energy = energy;
+        attacker
+    }
+
+    /// Two segments meeting at a joint that the attacker's segment crosses.
+    fn victim(first: SegmentType, second: SegmentType, energy: f32) -> Creature {
+        let mut victim = creature(
+            2,
+            glam::Vec2::ZERO,
+            vec![
+                segment((0.0, -10.0), (0.0, 0.0), first),
+                segment((0.0, 0.0), (0.0, 10.0), second),
+            ],
+        );
+        victim.energy = energy;
+        victim
+    }
+
+    /// Runs one second of combat, and returns the attacker's and victim's energy.
+    fn fight(creatures: &mut [Creature]) -> (f32, f32) {
+        let grid = SpatialGrid::build(glam::Vec2::new(100.0, 100.0), Boundary::Walls, creatures);
+        let contacts = find_contacts(creatures, &grid);
+        resolve_combat(creatures, &contacts, &Config::default(), 1.0);
+        (creatures[0].energy, creatures[1].energy)
+    }
+
+    #[test]
+    fn attacks_drain_once_per_victim() {
+        // Touching both segments at the joint still only drains 20 * 5.
+        let mut creatures = [
+            attacker(0.0),
+            victim(SegmentType::Energy, SegmentType::Move, 150.0),
+        ];
+        assert_eq!(fight(&mut creatures), (100.0, 50.0));
+        assert!(!creatures[1].dead);
+    }
+
+    #[test]
+    fn defend_segments_reduce_the_drain() {
+        // 90% of the drain is blocked, even though the other touched segment isn't Defend.
+        let mut creatures = [
+            attacker(0.0),
+            victim(SegmentType::Energy, SegmentType::Defend, 150.0),
+        ];
+        let (attacker_energy, victim_energy) = fight(&mut creatures);
+        assert!((attacker_energy - 10.0).abs() < 1e-4);
+        assert!((victim_energy - 140.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn attackers_cannot_store_more_than_their_capacity() {
+        let mut creatures = [
+            attacker(150.0),
+            victim(SegmentType::Energy, SegmentType::Energy, 150.0),
+        ];
+        assert_eq!(fight(&mut creatures), (200.0, 50.0));
+    }
+
+    #[test]
+    fn victims_drained_of_all_their_energy_die() {
+        let mut creatures = [
+            attacker(0.0),
+            victim(SegmentType::Energy, SegmentType::Energy, 30.0),
+        ];
+        assert_eq!(fight(&mut creatures), (30.0, 0.0));
+        assert!(creatures[1].dead);
+        assert_eq!(creatures[1].death_cause, Some(DeathCause::Attack));
+    }
+}
@@ -0,0 +1,690 @@
+//! Tunable parameters of the simulation.
+//!
+//! A config file is TOML or JSON, chosen by its extension. Every key is optional and falls back to
+//! its default, so a file only needs the values it changes. For example, in TOML:
+//!
+//! ```toml
+//! [world]
+//! size = [600.0, 400.0]
+//! boundary = "Toroidal"
+//!
+//! [creature]
+//! momentum_half_life = 1.0
+//!
+//! [mutation]
+//! insert_chance = 0.2
+//! ```
+
+use crate::{boundary::Boundary, environment::ResourceField};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub world: WorldConfig,
+    pub creature: CreatureConfig,
+    pub body: BodyConfig,
+    pub mutation: MutationConfig,
+    pub generator: GeneratorConfig,
+    pub combat: CombatConfig,
+    pub corpse: CorpseConfig,
+    pub fps_stats: FpsStatsConfig,
+    pub stats: StatsConfig,
+    pub species: SpeciesConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorldConfig {
+    /// Half the width and height of the world, in world units.
+    pub size: glam::Vec2,
+    pub boundary: Boundary,
+    pub resource_field: ResourceField,
+    /// Number of creatures to start with, unless genomes or a population are given on the
+    /// command line.
+    pub initial_population: usize,
+    /// Simulated seconds per update.
+    pub delta_time: f64,
+    /// Most updates to run for one frame. When updating falls further behind than this, the
+    /// simulation slows down rather than the frame rate dropping further.
+    pub max_steps_per_frame: u32,
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            size: glam::Vec2::new(400.0, 300.0),
+            boundary: Boundary::default(),
+            resource_field: ResourceField::default(),
+            initial_population: 20,
+            delta_time: 1.0 / 120.0,
+            max_steps_per_frame: 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CreatureConfig {
+    /// Half life (in seconds) of momentum and angular momentum.
+    pub momentum_half_life: f32,
+    /// Impulse of a move, for a body made only of Move segments.
+    pub movement_force: f32,
+    /// Moves per second, for a body made only of Move segments.
+    pub move_rate: f32,
+    /// Fraction of momentum kept after bouncing off a wall.
+    pub wall_bounce_damping: f32,
+    /// Typical speed of spawned creatures.
+    pub spawn_speed: f32,
+    /// Energy collected per second by each unit of length of an Energy segment, where the resource
+    /// intensity is 1.
+    pub energy_income_per_length: f32,
+    /// Energy spent per second by each unit of length of an Attack, Defend or Move segment.
+    pub upkeep_per_length: f32,
+    /// Energy spent per second per unit of body radius.
+    pub upkeep_per_radius: f32,
+    /// How much energy can be stored per unit of total segment length.
+    pub energy_capacity_per_length: f32,
+    /// A creature reproduces once its energy reaches this fraction of its capacity.
+    pub reproduction_threshold: f32,
+    /// Energy lost building each unit of segment length of a child's body.
+    pub reproduction_cost_per_length: f32,
+}
+
+impl Default for CreatureConfig {
+    fn default() -> Self {
+        Self {
+            momentum_half_life: 1.0,
+            movement_force: 100.0,
+            move_rate: 10.0,
+            wall_bounce_damping: 0.5,
+            spawn_speed: 100.0,
+            energy_income_per_length: 4.0,
+            upkeep_per_length: 1.0,
+            upkeep_per_radius: 1.0,
+            energy_capacity_per_length: 10.0,
+            reproduction_threshold: 0.9,
+            reproduction_cost_per_length: 2.0,
+        }
+    }
+}
+
+/// Limits on body plans. Mutations that break them are discarded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BodyConfig {
+    /// Segments shorter than this are degenerate.
+    pub min_segment_length: f32,
+    pub max_segments: usize,
+    /// How far any point of a body may be from the creature's origin.
+    pub max_radius: f32,
+}
+
+impl Default for BodyConfig {
+    fn default() -> Self {
+        Self {
+            min_segment_length: 1.0,
+            max_segments: 32,
+            max_radius: 150.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MutationConfig {
+    /// Chance that each distinct point in the body is moved.
+    pub jitter_chance: f32,
+    /// How far a jittered point moves, on average.
+    pub jitter_distance: f32,
+    /// Chance that each segment changes to a random type.
+    pub type_change_chance: f32,
+    /// Chance that a new segment is grown from an existing point.
+    pub insert_chance: f32,
+    /// Chance that a dangling segment is removed.
+    pub delete_chance: f32,
+}
+
+impl Default for MutationConfig {
+    fn default() -> Self {
+        Self {
+            jitter_chance: 0.2,
+            jitter_distance: 3.0,
+            type_change_chance: 0.05,
+            insert_chance: 0.1,
+            delete_chance: 0.1,
+        }
+    }
+}
+
+/// How random body plans are generated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeneratorConfig {
+    /// The number of segments is picked uniformly between these, inclusive.
+    pub min_segments: usize,
+    pub max_segments: usize,
+    /// Segment lengths are normally distributed.
+    pub mean_segment_length: f32,
+    pub segment_length_std_dev: f32,
+    pub type_weights: SegmentTypeWeights,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        Self {
+            min_segments: 2,
+            max_segments: 8,
+            mean_segment_length: 25.0,
+            segment_length_std_dev: 10.0,
+            type_weights: SegmentTypeWeights::default(),
+        }
+    }
+}
+
+/// Relative chance of each segment type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SegmentTypeWeights {
+    pub energy: f32,
+    pub attack: f32,
+    pub defend: f32,
+    #[serde(rename = "move")]
+    pub move_: f32,
+}
+
+impl Default for SegmentTypeWeights {
+    fn default() -> Self {
+        Self {
+            energy: 2.0,
+            attack: 1.0,
+            defend: 1.0,
+            move_: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CombatConfig {
+    /// Energy drained per second by each unit of length of an Attack segment.
+    pub attack_drain_rate: f32,
+    /// Fraction of the drain blocked when an Attack segment hits a Defend segment.
+    pub defend_block_fraction: f32,
+}
+
+impl Default for CombatConfig {
+    fn default() -> Self {
+        Self {
+            attack_drain_rate: 5.0,
+            defend_block_fraction: 0.9,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorpseConfig {
+    /// Energy a corpse holds per unit of segment length, on top of what the creature had left.
+    pub energy_per_length: f32,
+    /// Half life (in seconds) of the energy in a corpse.
+    pub half_life: f32,
+    /// Corpses holding less energy than this are gone. Must be positive, since decay alone never
+    /// reaches zero.
+    pub min_energy: f32,
+    /// Energy eaten per second by each unit of length of an Energy or Attack segment touching a
+    /// corpse.
+    pub scavenge_rate: f32,
+}
+
+impl Default for CorpseConfig {
+    fn default() -> Self {
+        Self {
+            energy_per_length: 2.0,
+            half_life: 30.0,
+            min_energy: 1.0,
+            scavenge_rate: 5.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FpsStatsConfig {
+    /// The half life (in seconds) of frame time samples.
+    pub half_life: f32,
+    /// Seconds between FPS log lines.
+    pub log_frequency: f32,
+}
+
+impl Default for FpsStatsConfig {
+    fn default() -> Self {
+        Self {
+            half_life: 1.0,
+            log_frequency: 10.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatsConfig {
+    /// Seconds of simulated time between population stats samples.
+    pub interval: f32,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self { interval: 1.0 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpeciesConfig {
+    /// Seconds of simulated time between clustering passes.
+    pub interval: f32,
+    /// Body plans closer than this belong to the same species.
+    pub threshold: f32,
+    /// Geometric differences are divided by this length before being added to the distance.
+    pub geometry_scale: f32,
+    /// Extra geometric distance between matched segments of different types.
+    pub type_mismatch_penalty: f32,
+}
+
+impl Default for SpeciesConfig {
+    fn default() -> Self {
+        Self {
+            interval: 5.0,
+            threshold: 0.5,
+            geometry_scale: 20.0,
+            type_mismatch_penalty: 5.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Toml(toml::de::Error),
+    /// A value is out of range. `key` is its dotted path, like `creature.move_rate`.
+    Invalid {
+        key: &'static str,
+        reason: &'static str,
+    },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "could not read or write config: {}", error),
+            ConfigError::Json(error) => write!(f, "invalid config: {}", error),
+            ConfigError::Toml(error) => write!(f, "invalid config: {}", error),
+            ConfigError::Invalid { key, reason } => {
+                write!(f, "invalid config: `{}` {}", key, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(error: std::io::Error) -> Self {
+        ConfigError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(error: serde_json::Error) -> Self {
+        ConfigError::Json(error)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(error: toml::de::Error) -> Self {
+        ConfigError::Toml(error)
+    }
+}
+
+fn positive(key: &'static str, value: f32) -> Result<(), ConfigError> {
+    if value > 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(ConfigError::Invalid {
+            key,
+            reason: "must be positive",
+        })
+    }
+}
+
+fn non_negative(key: &'static str, value: f32) -> Result<(), ConfigError> {
+    if value >= 0.0 && value.is_finite() {
+        Ok(())
+    } else {
+        Err(ConfigError::Invalid {
+            key,
+            reason: "must not be negative",
+        })
+    }
+}
+
+fn fraction(key: &'static str, value: f32) -> Result<(), ConfigError> {
+    if (0.0..=1.0).contains(&value) {
+        Ok(())
+    } else {
+        Err(ConfigError::Invalid {
+            key,
+            reason: "must be between 0 and 1",
+        })
+    }
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "toml")
+}
+
+impl Config {
+    /// Checks that every value is in range. The error names the first bad key.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let world = &self.world;
+        positive("world.size.x", world.size.x)?;
+        positive("world.size.y", world.size.y)?;
+        if !world.resource_field.is_valid() {
+            return Err(ConfigError::Invalid {
+                key: "world.resource_field",
+                reason: "must have finite, non-negative intensities and positive patch radii",
+            });
+        }
+        if !(world.delta_time > 0.0 && world.delta_time.is_finite()) {
+            return Err(ConfigError::Invalid {
+                key: "world.delta_time",
+                reason: "must be positive",
+            });
+        }
+        if world.max_steps_per_frame == 0 {
+            return Err(ConfigError::Invalid {
+                key: "world.max_steps_per_frame",
+                reason: "must be at least 1",
+            });
+        }
+        let creature = &self.creature;
+        positive("creature.momentum_half_life", creature.momentum_half_life)?;
+        non_negative("creature.movement_force", creature.movement_force)?;
+        non_negative("creature.move_rate", creature.move_rate)?;
+        fraction("creature.wall_bounce_damping", creature.wall_bounce_damping)?;
+        non_negative("creature.spawn_speed", creature.spawn_speed)?;
+        non_negative(
+            "creature.energy_income_per_length",
+            creature.energy_income_per_length,
+        )?;
+        non_negative("creature.upkeep_per_length", creature.upkeep_per_length)?;
+        non_negative("creature.upkeep_per_radius", creature.upkeep_per_radius)?;
+        positive(
+            "creature.energy_capacity_per_length",
+            creature.energy_capacity_per_length,
+        )?;
+        fraction(
+            "creature.reproduction_threshold",
+            creature.reproduction_threshold,
+        )?;
+        non_negative(
+            "creature.reproduction_cost_per_length",
+            creature.reproduction_cost_per_length,
+        )?;
+        let body = &self.body;
+        positive("body.min_segment_length", body.min_segment_length)?;
+        if body.max_segments == 0 {
+            return Err(ConfigError::Invalid {
+                key: "body.max_segments",
+                reason: "must be at least 1",
+            });
+        }
+        positive("body.max_radius", body.max_radius)?;
+        if body.max_radius < body.min_segment_length {
+            return Err(ConfigError::Invalid {
+                key: "body.max_radius",
+                reason: "must be at least `body.min_segment_length`",
+            });
+        }
+        let mutation = &self.mutation;
+        fraction("mutation.jitter_chance", mutation.jitter_chance)?;
+        non_negative("mutation.jitter_distance", mutation.jitter_distance)?;
+        fraction("mutation.type_change_chance", mutation.type_change_chance)?;
+        fraction("mutation.insert_chance", mutation.insert_chance)?;
+        fraction("mutation.delete_chance", mutation.delete_chance)?;
+        let generator = &self.generator;
+        if generator.min_segments == 0 {
+            return Err(ConfigError::Invalid {
+                key: "generator.min_segments",
+                reason: "must be at least 1",
+            });
+        }
+        if generator.max_segments < generator.min_segments {
+            return Err(ConfigError::Invalid {
+                key: "generator.max_segments",
+                reason: "must be at least `generator.min_segments`",
+            });
+        }
+        positive(
+            "generator.mean_segment_length",
+            generator.mean_segment_length,
+        )?;
+        non_negative(
+            "generator.segment_length_std_dev",
+            generator.segment_length_std_dev,
+        )?;
+        let weights = &generator.type_weights;
+        non_negative("generator.type_weights.energy", weights.energy)?;
+        non_negative("generator.type_weights.attack", weights.attack)?;
+        non_negative("generator.type_weights.defend", weights.defend)?;
+        non_negative("generator.type_weights.move", weights.move_)?;
+        if weights.energy + weights.attack + weights.defend + weights.move_ <= 0.0 {
+            return Err(ConfigError::Invalid {
+                key: "generator.type_weights",
+                reason: "must not all be zero",
+            });
+        }
+        non_negative("combat.attack_drain_rate", self.combat.attack_drain_rate)?;
+        fraction(
+            "combat.defend_block_fraction",
+            self.combat.defend_block_fraction,
+        )?;
+        let corpse = &self.corpse;
+        non_negative("corpse.energy_per_length", corpse.energy_per_length)?;
+        positive("corpse.half_life", corpse.half_life)?;
+        positive("corpse.min_energy", corpse.min_energy)?;
+        non_negative("corpse.scavenge_rate", corpse.scavenge_rate)?;
+        positive("fps_stats.half_life", self.fps_stats.half_life)?;
+        positive("fps_stats.log_frequency", self.fps_stats.log_frequency)?;
+        positive("stats.interval", self.stats.interval)?;
+        let species = &self.species;
+        positive("species.interval", species.interval)?;
+        positive("species.threshold", species.threshold)?;
+        positive("species.geometry_scale", species.geometry_scale)?;
+        non_negative(
+            "species.type_mismatch_penalty",
+            species.type_mismatch_penalty,
+        )?;
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn to_toml(&self) -> String {
+        // The TOML serializer widens f32 values to f64, which writes 0.1 as 0.10000000149011612.
+        // JSON writes each f32 as the shortest decimal that reads back the same, so the config
+        // goes through JSON to pick up those decimals.
+        let table: toml::Table = serde_json::from_str(&self.to_json()).unwrap();
+        toml::to_string_pretty(&table).unwrap()
+    }
+
+    /// Parses and validates a JSON config.
+    pub fn from_json(json: &str) -> Result<Self, ConfigError> {
+        let config: Config = serde_json::from_str(json)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parses and validates a TOML config.
+    pub fn from_toml(toml: &str) -> Result<Self, ConfigError> {
+        let config: Config = toml::from_str(toml)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Reads TOML if the path ends in `.toml`, and JSON otherwise.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        if is_toml(path) {
+            Self::from_toml(&text)
+        } else {
+            Self::from_json(&text)
+        }
+    }
+
+    /// Writes TOML if the path ends in `.toml`, and JSON otherwise.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        let text = if is_toml(path) {
+            self.to_toml()
+        } else {
+            self.to_json()
+        };
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::Patch;
+
+    fn invalid_key(change: impl FnOnce(&mut Config)) -> Option<&'static str> {
+        let mut config = Config::default();
+        change(&mut config);
+        match config.validate() {
+            Err(ConfigError::Invalid { key, .. }) => Some(key),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn default_is_valid() {
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn round_trips_through_toml_and_json() {
+        let mut config = Config::default();
+        config.world.boundary = Boundary::Toroidal;
+        config.world.resource_field = ResourceField::Patches {
+            background: 0.5,
+            patches: vec![Patch {
+                center: glam::Vec2::new(10.0, -20.0),
+                radius: 50.0,
+                intensity: 2.0,
+            }],
+        };
+        assert_eq!(Config::from_toml(&config.to_toml()).unwrap(), config);
+        assert_eq!(Config::from_json(&config.to_json()).unwrap(), config);
+    }
+
+    #[test]
+    fn toml_writes_f32_values_as_they_were_given() {
+        let mut config = Config::default();
+        config.creature.reproduction_threshold = 0.7;
+        let toml = config.to_toml();
+        assert!(
+            toml.contains("\nreproduction_threshold = 0.7\n"),
+            "{}",
+            toml
+        );
+        assert!(toml.contains("\ninsert_chance = 0.1\n"), "{}", toml);
+        // Sections keep the order of the fields.
+        assert!(toml.starts_with("[world]\n"), "{}", toml);
+    }
+
+    #[test]
+    fn missing_keys_take_defaults() {
+        let config = Config::from_toml("[world]\nsize = [100.0, 50.0]\n").unwrap();
+        assert_eq!(config.world.size, glam::Vec2::new(100.0, 50.0));
+        assert_eq!(config.creature, CreatureConfig::default());
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(matches!(
+            Config::from_toml("[world]\nsise = [100.0, 50.0]\n"),
+            Err(ConfigError::Toml(_))
+        ));
+    }
+
+    #[test]
+    fn out_of_range_values_name_their_key() {
+        type Change = fn(&mut Config);
+        let cases: [(&str, Change); 16] = [
+            ("world.size.x", |c| c.world.size.x = -1.0),
+            ("world.size.y", |c| c.world.size.y = f32::INFINITY),
+            ("world.resource_field", |c| {
+                c.world.resource_field = ResourceField::Patches {
+                    background: 0.0,
+                    patches: vec![Patch {
+                        center: glam::Vec2::ZERO,
+                        radius: 0.0,
+                        intensity: 1.0,
+                    }],
+                }
+            }),
+            ("world.delta_time", |c| c.world.delta_time = 0.0),
+            ("world.max_steps_per_frame", |c| {
+                c.world.max_steps_per_frame = 0
+            }),
+            ("creature.momentum_half_life", |c| {
+                c.creature.momentum_half_life = f32::NAN
+            }),
+            ("creature.wall_bounce_damping", |c| {
+                c.creature.wall_bounce_damping = 1.5
+            }),
+            ("body.min_segment_length", |c| {
+                c.body.min_segment_length = 0.0
+            }),
+            ("body.max_segments", |c| c.body.max_segments = 0),
+            ("body.max_radius", |c| c.body.max_radius = 0.5),
+            ("generator.max_segments", |c| {
+                c.generator.max_segments = c.generator.min_segments - 1
+            }),
+            ("generator.type_weights", |c| {
+                c.generator.type_weights = SegmentTypeWeights {
+                    energy: 0.0,
+                    attack: 0.0,
+                    defend: 0.0,
+                    move_: 0.0,
+                }
+            }),
+            ("corpse.half_life", |c| c.corpse.half_life = -1.0),
+            ("corpse.min_energy", |c| c.corpse.min_energy = 0.0),
+            ("stats.interval", |c| c.stats.interval = 0.0),
+            ("species.threshold", |c| c.species.threshold = 0.0),
+        ];
+        for (key, change) in cases {
+            assert_eq!(invalid_key(change), Some(key));
+        }
+    }
+
+    #[test]
+    fn parsed_configs_are_validated() {
+        let error = Config::from_toml("[mutation]\ninsert_chance = 2.0\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid config: `mutation.insert_chance` must be between 0 and 1"
+        );
+    }
+}
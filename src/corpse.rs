@@ -0,0 +1,235 @@
+use crate::{
+    boundary::Boundary,
+    collision::{segments_intersect, wrap_shift},
+    config::{Config, CorpseConfig},
+    creature::{indices_by_id, Creature, Segment, SegmentType},
+    spatial::SpatialGrid,
+};
+use serde::{Deserialize, Serialize};
+
+/// The remains of a dead creature. Corpses do not move, and their energy decays until it is eaten
+/// or gone.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Corpse {
+    /// Segments in world space.
+    pub segments: Vec<Segment>,
+    /// Bounding circle.
+    pub center: glam::Vec2,
+    pub radius: f32,
+    pub energy: f32,
+}
+
+impl Corpse {
+    pub fn new(creature: &Creature, config: &CorpseConfig) -> Self {
+        let segments = creature
+            .segments
+            .iter()
+            .map(|segment| {
+                let (a, b) = creature.world_segment(segment);
+                Segment { a, b, t: segment.t }
+            })
+            .collect();
+        Self {
+            segments,
+            center: creature.position,
+            radius: creature.radius(),
+            energy: creature.energy.max(0.0)
+                + creature.segment_lengths().total * config.energy_per_length,
+        }
+    }
+
+    pub fn decay(&mut self, delta_time: f32, config: &CorpseConfig) {
+        self.energy *= f32::exp(f32::ln(0.5) * delta_time / config.half_life);
+    }
+
+    pub fn is_gone(&self, config: &CorpseConfig) -> bool {
+        self.energy < config.min_energy
+    }
+}
+
+/// Living creatures eat from corpses their Energy or Attack segments touch. Creatures eat in ID
+/// order (see `indices_by_id`).
+pub fn scavenge(
+    creatures: &mut [Creature],
+    corpses: &mut [Corpse],
+    world_size: glam::Vec2,
+    boundary: Boundary,
+    config: &Config,
+    delta_time: f32,
+) {
+    if corpses.is_empty() {
+        return;
+    }
+    let corpse_grid = SpatialGrid::from_bounds(
+        world_size,
+        boundary,
+        corpses
+            .iter()
+            .map(|corpse| (corpse.center, corpse.radius))
+            .collect(),
+    );
+    for index in indices_by_id(creatures) {
+        let creature = &mut creatures[index];
+        if creature.dead {
+            continue;
+        }
+        for corpse_index in corpse_grid.query_circle(creature.position, creature.radius()) {
+            let corpse = &mut corpses[corpse_index];
+            let shift = wrap_shift(&corpse_grid, creature.position, corpse.center);
+            for segment in creature.segments.iter() {
+                if segment.t != SegmentType::Energy && segment.t != SegmentType::Attack {
+                    continue;
+                }
+                let (a, b) = creature.world_segment(segment);
+                let touching = corpse.segments.iter().any(|corpse_segment| {
+                    segments_intersect(a, b, corpse_segment.a + shift, corpse_segment.b + shift)
+                });
+                if touching {
+                    let room =
+                        (creature.energy_capacity(&config.creature) - creature.energy).max(0.0);
+                    let eaten = (segment.length() * config.corpse.scavenge_rate * delta_time)
+                        .min(corpse.energy)
+                        .min(room);
+                    corpse.energy -= eaten;
+                    creature.energy += eaten;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        creature::tests::{creature, segment},
+        genome::Genome,
+        world::World,
+    };
+
+    /// A 20 long vertical segment, crossing the one an `eater` at the same position has.
+    fn corpse(position: glam::Vec2, energy: f32) -> Corpse {
+        let body = creature(
+            9,
+            position,
+            vec![segment((0.0, -10.0), (0.0, 10.0), SegmentType::Defend)],
+        );
+        let mut corpse = Corpse::new(&body, &CorpseConfig::default());
+        corpse.energy = energy;
+        corpse
+    }
+
+    /// A 20 long segment, with a capacity of 200 energy. It eats up to 100 energy a second.
+    fn eater(position: glam::Vec2, t: SegmentType, energy: f32) -> Creature {
+        let mut eater = creature(1, position, vec![segment((-10.0, 0.0), (10.0, 0.0), t)]);
+        eater.energy = energy;
+        eater
+    }
+
+    /// Scavenges for one second, and returns the eater's and corpse's energy.
+    fn eat(eater: Creature, corpse: Corpse, boundary: Boundary) -> (f32, f32) {
+        let (mut creatures, mut corpses) = ([eater], [corpse]);
+        let world_size = glam::Vec2::new(100.0, 100.0);
+        scavenge(
+            &mut creatures,
+            &mut corpses,
+            world_size,
+            boundary,
+            &Config::default(),
+            1.0,
+        );
+        (creatures[0].energy, corpses[0].energy)
+    }
+
+    #[test]
+    fn corpses_hold_leftover_energy_and_their_body() {
+        let config = CorpseConfig::default();
+        let mut body = creature(
+            1,
+            glam::Vec2::new(5.0, 5.0),
+            vec![
+                segment((0.0, 0.0), (0.0, 20.0), SegmentType::Energy),
+                segment((0.0, 20.0), (10.0, 20.0), SegmentType::Move),
+            ],
+        );
+        body.energy = 30.0;
+        assert_eq!(Corpse::new(&body, &config).energy, 30.0 + 30.0 * 2.0);
+        // A creature that starved with negative energy doesn't take from the body's share.
+        body.energy = -5.0;
+        assert_eq!(Corpse::new(&body, &config).energy, 30.0 * 2.0);
+    }
+
+    #[test]
+    fn only_energy_and_attack_segments_eat() {
+        for (t, eats) in [
+            (SegmentType::Energy, true),
+            (SegmentType::Attack, true),
+            (SegmentType::Move, false),
+            (SegmentType::Defend, false),
+        ] {
+            let eaten = eat(
+                eater(glam::Vec2::ZERO, t, 0.0),
+                corpse(glam::Vec2::ZERO, 1000.0),
+                Boundary::Walls,
+            );
+            let expected = if eats { (100.0, 900.0) } else { (0.0, 1000.0) };
+            assert_eq!(eaten, expected, "{t:?}");
+        }
+    }
+
+    #[test]
+    fn eating_is_capped_by_spare_capacity_and_corpse_energy() {
+        let at = glam::Vec2::ZERO;
+        assert_eq!(
+            eat(
+                eater(at, SegmentType::Energy, 150.0),
+                corpse(at, 1000.0),
+                Boundary::Walls
+            ),
+            (200.0, 950.0)
+        );
+        assert_eq!(
+            eat(
+                eater(at, SegmentType::Energy, 0.0),
+                corpse(at, 30.0),
+                Boundary::Walls
+            ),
+            (30.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn scavenging_wraps_around_toroidal_edges() {
+        // The eater sticks out of the left edge, and the corpse's segment lies just inside the
+        // right edge, so they only touch across it.
+        let eater = eater(glam::Vec2::new(-95.0, 0.0), SegmentType::Energy, 0.0);
+        let corpse = corpse(glam::Vec2::new(98.0, 0.0), 1000.0);
+        assert_eq!(
+            eat(eater.clone(), corpse.clone(), Boundary::Walls),
+            (0.0, 1000.0)
+        );
+        assert_eq!(eat(eater, corpse, Boundary::Toroidal), (100.0, 900.0));
+    }
+
+    #[test]
+    fn world_update_removes_decayed_and_eaten_corpses() {
+        let mut config = Config::default();
+        config.corpse.min_energy = 0.01;
+        let mut world = World::new(0, config);
+        let genome = Genome {
+            segments: vec![segment((-10.0, 0.0), (10.0, 0.0), SegmentType::Energy)],
+        };
+        world.spawn(genome, glam::Vec2::ZERO).unwrap();
+        world.creatures[0].momentum = glam::Vec2::ZERO;
+        world.corpses = vec![
+            // Eaten within the first tick.
+            corpse(glam::Vec2::ZERO, 0.5),
+            // Decays below `min_energy` within the first tick.
+            corpse(glam::Vec2::new(-150.0, 0.0), 0.010001),
+            corpse(glam::Vec2::new(150.0, 0.0), 100.0),
+        ];
+        world.update();
+        assert_eq!(world.corpses.len(), 1);
+        assert_eq!(world.corpses[0].center, glam::Vec2::new(150.0, 0.0));
+    }
+}
@@ -1,7 +1,18 @@
-use rand::Rng as _;
+use crate::{
+    boundary::Boundary,
+    config::{Config, CreatureConfig},
+    environment::ResourceField,
+    genome::Genome,
+};
+use rand::Rng;
 use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The random number generator used for everything in the simulation. Each creature owns its own
+/// stream so that results do not depend on the order creatures are updated in.
+pub type SimRng = rand_pcg::Pcg32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SegmentType {
     Energy,
     Attack,
@@ -9,6 +20,26 @@ pub enum SegmentType {
     Move,
 }
 
+impl SegmentType {
+    pub const ALL: [SegmentType; 4] = [
+        SegmentType::Energy,
+        SegmentType::Attack,
+        SegmentType::Defend,
+        SegmentType::Move,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeathCause {
+    Starvation,
+    /// Poked through the edge of the world.
+    Wall,
+    /// Drained by another creature's Attack segment.
+    Attack,
+    /// Killed from outside the simulation.
+    Intervention,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct SegmentLengths {
     pub energy: f32,
@@ -18,10 +49,11 @@ pub struct SegmentLengths {
     pub total: f32,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Segment {
     pub a: glam::Vec2,
     pub b: glam::Vec2,
+    #[serde(rename = "type")]
     pub t: SegmentType,
 }
 
@@ -37,54 +69,170 @@ impl Segment {
     }
 
     pub fn midpoint(&self) -> glam::Vec2 {
-        (self.b - self.a) / 2.0
+        (self.a + self.b) / 2.0
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Creature {
+    /// Unique within a World. Also selects the creature's random stream.
+    pub id: u64,
+    /// The creature this one was born from. None for creatures that were spawned.
+    pub parent_id: Option<u64>,
+    pub rng: SimRng,
     pub segments: Vec<Segment>,
     pub position: glam::Vec2,
+    /// Position before the most recent update. Used to interpolate rendering between updates.
+    pub previous_position: glam::Vec2,
     pub momentum: glam::Vec2,
+    /// Rotation in radians, counterclockwise, about `position`.
+    pub angle: f32,
+    /// Angle before the most recent update. Used to interpolate rendering between updates.
+    pub previous_angle: f32,
+    /// Like momentum, this is used directly as the angular velocity in radians per second.
+    pub angular_momentum: f32,
     pub energy: f32,
     pub dead: bool,
+    pub death_cause: Option<DeathCause>,
 }
 
 impl Default for Creature {
     fn default() -> Self {
-        let mut segments = Vec::with_capacity(15);
-        segments.push(Segment {
-            a: glam::Vec2::new(0.0, 0.0),
-            b: glam::Vec2::new(0.0, 30.0),
-            t: SegmentType::Energy,
-        });
-        segments.push(Segment {
-            a: glam::Vec2::new(0.0, 30.0),
-            b: glam::Vec2::new(30.0, 30.0),
-            t: SegmentType::Move,
-        });
-        segments.push(Segment {
-            a: glam::Vec2::new(30.0, 30.0),
-            b: glam::Vec2::new(30.0, 0.0),
-            t: SegmentType::Attack,
-        });
         Self {
-            segments,
-            position: glam::Vec2::ZERO,
             momentum: glam::Vec2::new(100.0, 0.0),
-            energy: 0.0,
-            dead: false,
+            ..Self::new(0, SimRng::new(0, 0), Genome::default(), glam::Vec2::ZERO)
         }
     }
 }
 
-pub fn random_normal_vec2() -> glam::Vec2 {
-    let mut rng = rand::thread_rng();
+pub fn random_normal_vec2(rng: &mut impl Rng) -> glam::Vec2 {
     let normal = Normal::new(0.0, 1.0).unwrap();
-    let normal_vec2 = glam::Vec2::new(normal.sample(&mut rng), normal.sample(&mut rng));
+    let normal_vec2 = glam::Vec2::new(normal.sample(rng), normal.sample(rng));
     normal_vec2.normalize_or_zero()
 }
 
+/// Indices of the creatures, in order of their IDs. Anything where one creature's effect depends on
+/// what others did before it is done in this order, so results don't depend on the order creatures
+/// are stored in.
+pub fn indices_by_id(creatures: &[Creature]) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..creatures.len()).collect();
+    indices.sort_unstable_by_key(|&index| creatures[index].id);
+    indices
+}
+
 impl Creature {
+    pub fn new(id: u64, rng: SimRng, genome: Genome, position: glam::Vec2) -> Self {
+        Self {
+            id,
+            parent_id: None,
+            rng,
+            segments: genome.segments,
+            position,
+            previous_position: position,
+            momentum: glam::Vec2::ZERO,
+            angle: 0.0,
+            previous_angle: 0.0,
+            angular_momentum: 0.0,
+            energy: 0.0,
+            dead: false,
+            death_cause: None,
+        }
+    }
+
+    pub fn genome(&self) -> Genome {
+        Genome {
+            segments: self.segments.clone(),
+        }
+    }
+
+    /// Rotates a point in the creature's body by the creature's angle.
+    pub fn rotate(&self, point: glam::Vec2) -> glam::Vec2 {
+        glam::Vec2::from_angle(self.angle).rotate(point)
+    }
+
+    /// A point in the creature's body in world space.
+    pub fn world_point(&self, point: glam::Vec2) -> glam::Vec2 {
+        self.position + self.rotate(point)
+    }
+
+    /// The endpoints of one of this creature's segments in world space.
+    pub fn world_segment(&self, segment: &Segment) -> (glam::Vec2, glam::Vec2) {
+        (self.world_point(segment.a), self.world_point(segment.b))
+    }
+
+    /// Position interpolated between the previous and current update. `alpha` is from 0.0 to 1.0.
+    pub fn interpolated_position(&self, alpha: f32) -> glam::Vec2 {
+        self.previous_position.lerp(self.position, alpha)
+    }
+
+    /// The endpoints of one of this creature's segments in world space, interpolated between the
+    /// previous and current update. `alpha` is from 0.0 to 1.0.
+    pub fn interpolated_world_segment(
+        &self,
+        segment: &Segment,
+        alpha: f32,
+    ) -> (glam::Vec2, glam::Vec2) {
+        let position = self.interpolated_position(alpha);
+        let rotation = glam::Vec2::from_angle(
+            self.previous_angle + (self.angle - self.previous_angle) * alpha,
+        );
+        (
+            position + rotation.rotate(segment.a),
+            position + rotation.rotate(segment.b),
+        )
+    }
+
+    /// The center of mass in body coordinates, treating segments as rods of uniform density.
+    pub fn center_of_mass(&self) -> glam::Vec2 {
+        let total_length = self.segment_lengths().total;
+        if total_length == 0.0 {
+            return glam::Vec2::ZERO;
+        }
+        self.segments
+            .iter()
+            .map(|segment| segment.midpoint() * segment.length())
+            .sum::<glam::Vec2>()
+            / total_length
+    }
+
+    /// Moment of inertia about the center of mass, per unit mass. This is how hard the body is to
+    /// spin relative to how hard it is to push.
+    pub fn moment_of_inertia(&self) -> f32 {
+        let total_length = self.segment_lengths().total;
+        if total_length == 0.0 {
+            return 1.0;
+        }
+        let center_of_mass = self.center_of_mass();
+        let inertia: f32 = self
+            .segments
+            .iter()
+            .map(|segment| {
+                let length = segment.length();
+                // A rod about its own midpoint, moved to the center of mass by the parallel axis
+                // theorem.
+                length
+                    * (length * length / 12.0 + segment.midpoint().distance_squared(center_of_mass))
+            })
+            .sum();
+        (inertia / total_length).max(1.0)
+    }
+
+    /// Rotates the creature about its center of mass.
+    pub fn turn(&mut self, delta_angle: f32) {
+        let center_of_mass = self.center_of_mass();
+        let pivot = self.rotate(center_of_mass);
+        self.angle += delta_angle;
+        self.position += pivot - self.rotate(center_of_mass);
+    }
+
+    /// Pushes the creature at a point in its body. `point` is in body coordinates and `impulse` is
+    /// in world coordinates. Pushes away from the center of mass also spin the creature.
+    pub fn apply_impulse(&mut self, point: glam::Vec2, impulse: glam::Vec2) {
+        self.momentum += impulse;
+        let lever = self.rotate(point - self.center_of_mass());
+        self.angular_momentum += lever.perp_dot(impulse) / self.moment_of_inertia();
+    }
+
     pub fn radius(&self) -> f32 {
         let mut radius_squared = 0.0;
         for segment in self.segments.iter() {
@@ -116,54 +264,185 @@ impl Creature {
         segment_lengths
     }
 
-    pub fn energy_income(&self) -> f32 {
-        self.segment_lengths().energy
+    /// Energy collected per second. Each Energy segment collects in proportion to its length and
+    /// the resource intensity at its midpoint.
+    pub fn energy_income(
+        &self,
+        resource_field: &ResourceField,
+        world_size: glam::Vec2,
+        boundary: Boundary,
+        config: &CreatureConfig,
+    ) -> f32 {
+        self.segments
+            .iter()
+            .filter(|segment| segment.t == SegmentType::Energy)
+            .map(|segment| {
+                let midpoint = self.world_point(segment.midpoint());
+                segment.length()
+                    * config.energy_income_per_length
+                    * resource_field.sample(midpoint, world_size, boundary)
+            })
+            .sum()
     }
 
-    pub fn energy_requirement(&self) -> f32 {
+    /// Energy spent per second on upkeep of the non-Energy segments and the size of the body.
+    pub fn energy_requirement(&self, config: &CreatureConfig) -> f32 {
         let segment_lengths = self.segment_lengths();
-        segment_lengths.attack + segment_lengths.defend + segment_lengths.move_ + self.radius()
+        (segment_lengths.attack + segment_lengths.defend + segment_lengths.move_)
+            * config.upkeep_per_length
+            + self.radius() * config.upkeep_per_radius
+    }
+
+    /// The most energy this creature can store. Bigger bodies can store more.
+    pub fn energy_capacity(&self, config: &CreatureConfig) -> f32 {
+        self.segment_lengths().total * config.energy_capacity_per_length
+    }
+
+    /// Integrates net energy over the time step. The creature starves if its energy runs out.
+    pub fn update_energy(
+        &mut self,
+        delta_time: f32,
+        world_size: glam::Vec2,
+        boundary: Boundary,
+        resource_field: &ResourceField,
+        config: &CreatureConfig,
+    ) {
+        let net_energy = self.energy_income(resource_field, world_size, boundary, config)
+            - self.energy_requirement(config);
+        self.energy = (self.energy + net_energy * delta_time).min(self.energy_capacity(config));
+        if self.energy < 0.0 {
+            self.energy = 0.0;
+            self.kill(DeathCause::Starvation);
+        }
+    }
+
+    /// Marks the creature dead. Only the first cause of death is kept.
+    pub fn kill(&mut self, cause: DeathCause) {
+        if !self.dead {
+            self.dead = true;
+            self.death_cause = Some(cause);
+        }
+    }
+
+    pub fn can_reproduce(&self, config: &CreatureConfig) -> bool {
+        !self.dead && self.energy >= self.energy_capacity(config) * config.reproduction_threshold
     }
 
-    pub fn maybe_move(&mut self, delta_time: f32) {
+    /// Moves the creature into the world: wrapped around in a toroidal world, and with its whole
+    /// body inside the walls otherwise, as far as the world is big enough for it.
+    pub fn place_inside(&mut self, world_size: glam::Vec2, boundary: Boundary) {
+        self.position = match boundary {
+            Boundary::Walls => {
+                // A body poking through a wall would bounce against it from the outside forever.
+                let margin = (world_size - glam::Vec2::splat(self.radius())).max(glam::Vec2::ZERO);
+                self.position.clamp(-margin, margin)
+            }
+            Boundary::Toroidal => boundary.wrap(self.position, world_size),
+        };
+        self.previous_position = self.position;
+    }
+
+    /// Produces a mutated child next to this creature. Half of this creature's energy goes to the
+    /// child, less the cost of building the child's body. If that leaves the child no energy, there
+    /// is no child and this creature keeps its energy. If the mutated body plan is invalid, the
+    /// child is an exact copy instead. The child is placed inside the world (see `place_inside`).
+    pub fn reproduce(
+        &mut self,
+        child_id: u64,
+        child_rng: SimRng,
+        world_size: glam::Vec2,
+        boundary: Boundary,
+        config: &Config,
+    ) -> Option<Creature> {
+        let mut genome = self.genome();
+        genome.mutate(&config.mutation, &mut self.rng);
+        if genome.validate(&config.body).is_err() {
+            genome = self.genome();
+        }
+        let offset = random_normal_vec2(&mut self.rng) * self.radius() * 2.0;
+        let mut child = Creature::new(child_id, child_rng, genome, self.position + offset);
+        child.place_inside(world_size, boundary);
+        child.parent_id = Some(self.id);
+        child.momentum = self.momentum;
+        let child_energy = self.energy / 2.0;
+        let build_cost =
+            child.segment_lengths().total * config.creature.reproduction_cost_per_length;
+        if child_energy <= build_cost {
+            return None;
+        }
+        self.energy -= child_energy;
+        child.energy = child_energy - build_cost;
+        Some(child)
+    }
+
+    pub fn maybe_move(&mut self, delta_time: f32, config: &CreatureConfig) {
         let segment_lengths = self.segment_lengths();
         let movement_chance = segment_lengths.move_ / segment_lengths.total;
-        let movement_force = movement_chance * 100.0;
-        if rand::thread_rng().gen::<f32>() < movement_chance * 10.0 * delta_time {
-            if rand::thread_rng().gen::<bool>() {
-                self.momentum += random_normal_vec2() * movement_force;
+        let movement_force = movement_chance * config.movement_force;
+        let mut move_impulse = None;
+        if self.rng.gen::<f32>() < movement_chance * config.move_rate * delta_time {
+            if self.rng.gen::<bool>() {
+                self.momentum += random_normal_vec2(&mut self.rng) * movement_force;
             } else {
-                let mut r = rand::thread_rng().gen::<f32>() * segment_lengths.move_;
-                for segment in self.segments.iter() {
-                    if segment.length() < r {
-                        r -= segment.length();
-                    } else {
-                        assert!(segment.length() >= r);
-                        let move_direction =
-                            (segment.b - segment.a) * (r / segment.length()) + segment.a;
-                        if rand::thread_rng().gen::<bool>() {
-                            self.momentum += move_direction.normalize() * movement_force;
-                        } else {
-                            self.momentum += -move_direction.normalize() * movement_force;
-                        }
-                        break;
-                    }
+                move_impulse = self.random_move_impulse(segment_lengths.move_, movement_force);
+            }
+        }
+        if let Some((move_point, impulse)) = move_impulse {
+            self.apply_impulse(move_point, impulse);
+        }
+    }
+
+    /// Picks a random point on the Move segments, which are `move_length` long in all, and an
+    /// impulse pushing straight toward or away from the center there.
+    fn random_move_impulse(
+        &mut self,
+        move_length: f32,
+        movement_force: f32,
+    ) -> Option<(glam::Vec2, glam::Vec2)> {
+        let mut r = self.rng.gen::<f32>() * move_length;
+        for segment in self.segments.iter().filter(|s| s.t == SegmentType::Move) {
+            if segment.length() < r {
+                r -= segment.length();
+            } else {
+                let move_point = (segment.b - segment.a) * (r / segment.length()) + segment.a;
+                let mut impulse = self.rotate(move_point.normalize_or_zero()) * movement_force;
+                if self.rng.gen::<bool>() {
+                    impulse = -impulse;
                 }
+                return Some((move_point, impulse));
             }
         }
+        None
     }
 
-    pub fn check_wall_collision(&mut self, delta_time: f32, world_size: glam::Vec2) {
+    pub fn check_wall_collision(
+        &mut self,
+        delta_time: f32,
+        world_size: glam::Vec2,
+        config: &CreatureConfig,
+    ) {
         let mut bounced = (false, false);
+        let mut poked_through = false;
+        // Where the body will be after `update` moves it and turns it about its center of mass.
+        let center_of_mass = self.center_of_mass();
+        let pivot = self.world_point(center_of_mass);
+        let next_rotation = glam::Vec2::from_angle(self.angle + self.angular_momentum * delta_time);
         for segment in self.segments.iter() {
             for endpoint in [segment.a, segment.b] {
-                if !bounced.0 && (endpoint.x < -world_size.x || world_size.x < endpoint.x) {
-                    self.dead = true;
+                let world_endpoint = self.world_point(endpoint);
+                if !bounced.0
+                    && (world_endpoint.x < -world_size.x || world_size.x < world_endpoint.x)
+                {
+                    poked_through = true;
                 }
-                if !bounced.1 && (endpoint.y < -world_size.y || world_size.y < endpoint.y) {
-                    self.dead = true;
+                if !bounced.1
+                    && (world_endpoint.y < -world_size.y || world_size.y < world_endpoint.y)
+                {
+                    poked_through = true;
                 }
-                let next_endpoint = self.position + endpoint + self.momentum * delta_time;
+                let next_endpoint = pivot
+                    + next_rotation.rotate(endpoint - center_of_mass)
+                    + self.momentum * delta_time;
                 if !bounced.0 && (next_endpoint.x < -world_size.x || world_size.x < next_endpoint.x)
                 {
                     bounced.0 = true;
@@ -176,16 +455,185 @@ impl Creature {
                 }
             }
         }
+        if poked_through {
+            self.kill(DeathCause::Wall);
+        }
         if bounced.0 || bounced.1 {
-            self.momentum *= 0.5;
+            self.momentum *= config.wall_bounce_damping;
+            self.angular_momentum *= -config.wall_bounce_damping;
         }
     }
 
-    pub fn update(&mut self, delta_time: f32, world_size: glam::Vec2) {
-        self.maybe_move(delta_time);
-        self.check_wall_collision(delta_time, world_size);
+    pub fn update(
+        &mut self,
+        delta_time: f32,
+        world_size: glam::Vec2,
+        boundary: Boundary,
+        resource_field: &ResourceField,
+        config: &CreatureConfig,
+    ) {
+        self.previous_position = self.position;
+        self.previous_angle = self.angle;
+        self.update_energy(delta_time, world_size, boundary, resource_field, config);
+        self.maybe_move(delta_time, config);
+        if boundary == Boundary::Walls {
+            self.check_wall_collision(delta_time, world_size, config);
+        }
         self.position += self.momentum * delta_time;
+        self.turn(self.angular_momentum * delta_time);
+        let wrapped_position = boundary.wrap(self.position, world_size);
+        // Move the previous position too, so rendering doesn't interpolate across the world.
+        self.previous_position += wrapped_position - self.position;
+        self.position = wrapped_position;
         // We use the continuous time exponential growth function: P = P0 e^(kt)
-        self.momentum *= f32::exp(f32::ln(0.5) * delta_time);
+        let decay = f32::exp(f32::ln(0.5) * delta_time / config.momentum_half_life);
+        self.momentum *= decay;
+        self.angular_momentum *= decay;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::config::MutationConfig;
+
+    pub(crate) fn segment(a: (f32, f32), b: (f32, f32), t: SegmentType) -> Segment {
+        Segment {
+            a: glam::Vec2::new(a.0, a.1),
+            b: glam::Vec2::new(b.0, b.1),
+            t,
+        }
+    }
+
+    /// A creature with no energy, built from `segments` as they are in the genome.
+    pub(crate) fn creature(id: u64, position: glam::Vec2, segments: Vec<Segment>) -> Creature {
+        Creature::new(id, SimRng::new(0, id), Genome { segments }, position)
+    }
+
+    #[test]
+    fn spinning_bodies_bounce_off_walls() {
+        // An L shape just inside the wall. Spinning about its center of mass would swing its far
+        // ends through the wall, further than they reach from its position.
+        let segments = vec![
+            segment((0.0, 0.0), (140.0, 0.0), SegmentType::Energy),
+            segment((0.0, 0.0), (0.0, 140.0), SegmentType::Energy),
+        ];
+        let config = Config::default();
+        let world_size = config.world.size;
+        let mut creature = creature(1, glam::Vec2::new(258.0, 0.0), segments);
+        creature.energy = creature.energy_capacity(&config.creature) / 2.0;
+        creature.angular_momentum = 3.0;
+        for _ in 0..1200 {
+            creature.update(
+                config.world.delta_time as f32,
+                world_size,
+                Boundary::Walls,
+                &config.world.resource_field,
+                &config.creature,
+            );
+            assert!(!creature.dead);
+            for segment in creature.segments.iter() {
+                let (a, b) = creature.world_segment(segment);
+                assert!(a.abs().cmple(world_size).all() && b.abs().cmple(world_size).all());
+            }
+        }
+    }
+
+    #[test]
+    fn move_impulses_push_on_move_segments() {
+        // Most of the body is a long Energy arm that comes first, so a walk over every segment
+        // would usually land on it.
+        let mut creature = creature(
+            1,
+            glam::Vec2::ZERO,
+            vec![
+                segment((0.0, 0.0), (100.0, 0.0), SegmentType::Energy),
+                segment((0.0, 0.0), (0.0, -20.0), SegmentType::Move),
+            ],
+        );
+        let move_length = creature.segment_lengths().move_;
+        for _ in 0..1000 {
+            let (move_point, impulse) = creature.random_move_impulse(move_length, 1.0).unwrap();
+            assert!(move_point.x == 0.0 && (-20.0..=0.0).contains(&move_point.y));
+            assert!((impulse.length() - 1.0).abs() < 1e-4 || move_point == glam::Vec2::ZERO);
+        }
+    }
+
+    /// A config where bodies don't mutate.
+    fn exact_copies() -> Config {
+        Config {
+            mutation: MutationConfig {
+                jitter_chance: 0.0,
+                type_change_chance: 0.0,
+                insert_chance: 0.0,
+                delete_chance: 0.0,
+                ..MutationConfig::default()
+            },
+            ..Config::default()
+        }
+    }
+
+    /// A 20 long Energy segment, costing 40 energy to build.
+    fn parent(position: glam::Vec2, energy: f32) -> Creature {
+        let mut parent = creature(
+            1,
+            position,
+            vec![segment((-10.0, 0.0), (10.0, 0.0), SegmentType::Energy)],
+        );
+        parent.energy = energy;
+        parent
+    }
+
+    fn child_of(parent: &mut Creature, config: &Config) -> Option<Creature> {
+        let world_size = config.world.size;
+        parent.reproduce(2, SimRng::new(0, 2), world_size, Boundary::Walls, config)
+    }
+
+    #[test]
+    fn parents_split_their_energy_with_the_child() {
+        let config = exact_copies();
+        let mut parent = parent(glam::Vec2::ZERO, 180.0);
+        let child = child_of(&mut parent, &config).unwrap();
+        assert_eq!(parent.energy, 90.0);
+        assert_eq!(child.energy, 90.0 - 40.0);
+        assert_eq!((child.id, child.parent_id), (2, Some(1)));
+    }
+
+    #[test]
+    fn parents_that_cannot_pay_for_the_body_have_no_child() {
+        let config = exact_copies();
+        let mut parent = parent(glam::Vec2::ZERO, 80.0);
+        assert!(child_of(&mut parent, &config).is_none());
+        assert_eq!(parent.energy, 80.0);
+    }
+
+    #[test]
+    fn invalid_mutations_give_exact_copies() {
+        // Every mutation grows a segment, which the body limits don't allow.
+        let mut config = exact_copies();
+        config.mutation.insert_chance = 1.0;
+        config.body.max_segments = 1;
+        let mut parent = parent(glam::Vec2::ZERO, 180.0);
+        for _ in 0..100 {
+            parent.energy = 180.0;
+            let child = child_of(&mut parent, &config).unwrap();
+            assert_eq!(child.segments, parent.segments);
+        }
+    }
+
+    #[test]
+    fn children_are_placed_inside_the_walls() {
+        let config = Config::default();
+        let world_size = config.world.size;
+        // In a corner, so most children would land partly outside.
+        let mut parent = parent(world_size - glam::Vec2::splat(11.0), 180.0);
+        for _ in 0..100 {
+            parent.energy = 180.0;
+            let child = child_of(&mut parent, &config).unwrap();
+            for segment in child.segments.iter() {
+                let (a, b) = child.world_segment(segment);
+                assert!(a.abs().cmple(world_size).all() && b.abs().cmple(world_size).all());
+            }
+        }
     }
 }
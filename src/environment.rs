@@ -0,0 +1,173 @@
+use crate::boundary::Boundary;
+use serde::{Deserialize, Serialize};
+
+/// A bright or nutrient rich spot in a `ResourceField::Patches` field.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Patch {
+    pub center: glam::Vec2,
+    /// Distance at which the patch has fallen to half its peak intensity.
+    pub radius: f32,
+    pub intensity: f32,
+}
+
+/// Resource intensity (light, nutrients) over the world. Energy segments collect energy in
+/// proportion to the intensity where they are.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ResourceField {
+    /// The same intensity everywhere.
+    Uniform { intensity: f32 },
+    /// Intensity varies linearly from `from_intensity` at `from` to `to_intensity` at `to`, and
+    /// stays constant beyond them.
+    Gradient {
+        from: glam::Vec2,
+        to: glam::Vec2,
+        from_intensity: f32,
+        to_intensity: f32,
+    },
+    /// A background intensity with patches added on top.
+    Patches {
+        background: f32,
+        patches: Vec<Patch>,
+    },
+}
+
+impl Default for ResourceField {
+    fn default() -> Self {
+        ResourceField::Uniform { intensity: 1.0 }
+    }
+}
+
+impl ResourceField {
+    /// Whether every intensity is finite and non-negative and every patch has a positive radius.
+    pub fn is_valid(&self) -> bool {
+        let intensity = |value: f32| value.is_finite() && value >= 0.0;
+        match self {
+            ResourceField::Uniform { intensity: value } => intensity(*value),
+            ResourceField::Gradient {
+                from,
+                to,
+                from_intensity,
+                to_intensity,
+            } => {
+                from.is_finite()
+                    && to.is_finite()
+                    && intensity(*from_intensity)
+                    && intensity(*to_intensity)
+            }
+            ResourceField::Patches {
+                background,
+                patches,
+            } => {
+                intensity(*background)
+                    && patches.iter().all(|patch| {
+                        patch.center.is_finite()
+                            && patch.radius.is_finite()
+                            && patch.radius > 0.0
+                            && intensity(patch.intensity)
+                    })
+            }
+        }
+    }
+
+    /// The intensity at a point in world space. Distances are measured the short way round in a
+    /// toroidal world, so patches and gradients carry on across the edges.
+    pub fn sample(&self, point: glam::Vec2, world_size: glam::Vec2, boundary: Boundary) -> f32 {
+        match self {
+            ResourceField::Uniform { intensity } => *intensity,
+            ResourceField::Gradient {
+                from,
+                to,
+                from_intensity,
+                to_intensity,
+            } => {
+                let direction = *to - *from;
+                let length_squared = direction.length_squared();
+                if length_squared == 0.0 {
+                    return *from_intensity;
+                }
+                let offset = boundary.offset(*from, point, world_size);
+                let t = (offset.dot(direction) / length_squared).clamp(0.0, 1.0);
+                from_intensity + (to_intensity - from_intensity) * t
+            }
+            ResourceField::Patches {
+                background,
+                patches,
+            } => {
+                background
+                    + patches
+                        .iter()
+                        .map(|patch| {
+                            let distance_squared =
+                                boundary.distance_squared(point, patch.center, world_size);
+                            let radius_squared = patch.radius * patch.radius;
+                            // Gaussian falloff, at half intensity when distance equals radius.
+                            patch.intensity
+                                * f32::exp(f32::ln(0.5) * distance_squared / radius_squared)
+                        })
+                        .sum::<f32>()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORLD_SIZE: glam::Vec2 = glam::Vec2::new(100.0, 100.0);
+
+    fn patch(center: glam::Vec2) -> ResourceField {
+        ResourceField::Patches {
+            background: 0.5,
+            patches: vec![Patch {
+                center,
+                radius: 10.0,
+                intensity: 2.0,
+            }],
+        }
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn patches_are_at_half_intensity_at_their_radius() {
+        let center = glam::Vec2::new(20.0, -30.0);
+        let field = patch(center);
+        let sample = |point| field.sample(point, WORLD_SIZE, Boundary::Walls);
+        assert!(close(sample(center), 2.5));
+        for direction in [glam::Vec2::X, glam::Vec2::NEG_Y, glam::Vec2::new(0.6, 0.8)] {
+            assert!(close(sample(center + direction * 10.0), 1.5));
+        }
+    }
+
+    #[test]
+    fn gradients_are_constant_beyond_their_ends() {
+        let field = ResourceField::Gradient {
+            from: glam::Vec2::new(-50.0, 0.0),
+            to: glam::Vec2::new(50.0, 0.0),
+            from_intensity: 1.0,
+            to_intensity: 3.0,
+        };
+        let sample = |x, y| field.sample(glam::Vec2::new(x, y), WORLD_SIZE, Boundary::Walls);
+        assert!(close(sample(-90.0, 0.0), 1.0));
+        assert!(close(sample(-50.0, 40.0), 1.0));
+        assert!(close(sample(0.0, 0.0), 2.0));
+        assert!(close(sample(0.0, -40.0), 2.0));
+        assert!(close(sample(50.0, 0.0), 3.0));
+        assert!(close(sample(90.0, 20.0), 3.0));
+    }
+
+    #[test]
+    fn patches_reach_across_toroidal_edges() {
+        // 10 away across the right edge, and 190 away the long way round.
+        let field = patch(glam::Vec2::new(95.0, 0.0));
+        let point = glam::Vec2::new(-95.0, 0.0);
+        assert!(close(
+            field.sample(point, WORLD_SIZE, Boundary::Toroidal),
+            1.5
+        ));
+        assert!(close(field.sample(point, WORLD_SIZE, Boundary::Walls), 0.5));
+    }
+}
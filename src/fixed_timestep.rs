@@ -0,0 +1,88 @@
+/// Turns variable frame times into a whole number of fixed simulation steps.
+///
+/// Leftover time is carried over to the next frame, and `alpha` says how far between the last two
+/// simulation steps the current frame is, so rendering can interpolate.
+pub struct FixedTimestep {
+    /// Simulation step in seconds
+    dt: f32,
+    /// At most this many steps are run per frame. Time beyond that is dropped, so a long hitch
+    /// slows the simulation down instead of stalling the program trying to catch up.
+    max_steps_per_frame: u32,
+    accumulator: f32,
+}
+
+impl FixedTimestep {
+    pub fn new(dt: f32, max_steps_per_frame: u32) -> Self {
+        assert!(dt > 0.0);
+        Self {
+            dt,
+            max_steps_per_frame,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn dt(&self) -> f32 {
+        self.dt
+    }
+
+    /// Adds the frame time and returns how many simulation steps should be run.
+    pub fn advance(&mut self, frame_time: f32) -> u32 {
+        self.accumulator += frame_time;
+        let steps = (self.accumulator / self.dt) as u32;
+        if steps > self.max_steps_per_frame {
+            self.accumulator %= self.dt;
+            return self.max_steps_per_frame;
+        }
+        self.accumulator -= steps as f32 * self.dt;
+        steps
+    }
+
+    /// How far the current frame is between the previous and current simulation step, from 0.0 to
+    /// 1.0.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.dt).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_frames_are_split_into_several_steps() {
+        let mut timestep = FixedTimestep::new(0.25, 8);
+        assert_eq!(timestep.advance(1.0), 4);
+        assert_eq!(timestep.alpha(), 0.0);
+        // The leftover 0.125 is carried over to the next frame.
+        assert_eq!(timestep.advance(0.625), 2);
+        assert_eq!(timestep.alpha(), 0.5);
+        assert_eq!(timestep.advance(0.125), 1);
+    }
+
+    #[test]
+    fn time_beyond_the_step_limit_is_dropped() {
+        let mut timestep = FixedTimestep::new(0.25, 3);
+        assert_eq!(timestep.advance(10.125), 3);
+        assert_eq!(timestep.alpha(), 0.5);
+        assert_eq!(timestep.advance(0.0), 0);
+    }
+
+    #[test]
+    fn frame_rate_does_not_change_the_simulation_rate() {
+        for fps in [30.0, 60.0, 144.0, 500.0] {
+            let mut timestep = FixedTimestep::new(1.0 / 120.0, 8);
+            let mut steps = 0;
+            for _ in 0..(2.0 * fps) as u32 {
+                steps += timestep.advance(1.0 / fps);
+                assert!((0.0..=1.0).contains(&timestep.alpha()));
+            }
+            // Two seconds, give or take a step of rounding.
+            assert!(
+                (239..=240).contains(&steps),
+                "{} steps at {} FPS",
+                steps,
+                fps
+            );
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Random body plans, for seeding a world with a diverse population.
+
+use crate::{
+    body_plan::{segments_clash, validate_body_plan},
+    config::{BodyConfig, GeneratorConfig},
+    creature::{random_normal_vec2, Segment, SegmentType},
+};
+use rand::{distributions::WeightedIndex, seq::SliceRandom as _, Rng};
+use rand_distr::{Distribution, Normal};
+
+/// Tries at placing each segment before giving up on growing the body further.
+const PLACEMENT_ATTEMPTS: usize = 20;
+
+/// Grows a random connected body plan from the origin, one segment at a time. Each segment starts
+/// at an existing point and is kept only if it stays within the body limits and doesn't cross the
+/// rest of the body, so the result always passes `validate_body_plan`. Crowded bodies may end up
+/// with fewer segments than asked for, but always have at least one, as long as the limits pass
+/// `Config::validate`.
+pub fn generate(
+    generator: &GeneratorConfig,
+    body: &BodyConfig,
+    rng: &mut impl Rng,
+) -> Vec<Segment> {
+    let max_segments = generator.max_segments.min(body.max_segments);
+    let count = rng.gen_range(generator.min_segments.min(max_segments)..=max_segments);
+    let weights = &generator.type_weights;
+    let types = WeightedIndex::new([
+        weights.energy,
+        weights.attack,
+        weights.defend,
+        weights.move_,
+    ])
+    .unwrap();
+    let lengths = Normal::new(
+        generator.mean_segment_length,
+        generator.segment_length_std_dev,
+    )
+    .unwrap();
+    // Any length in this range fits when grown straight out from the origin.
+    let min_length = body.min_segment_length.max(f32::EPSILON);
+    let max_length = body.max_radius.max(min_length);
+    let mut segments: Vec<Segment> = Vec::with_capacity(count);
+    let mut points = vec![glam::Vec2::ZERO];
+    for _ in 0..count {
+        let mut placed = None;
+        for _ in 0..PLACEMENT_ATTEMPTS {
+            let a = *points.choose(rng).unwrap();
+            let length = lengths.sample(rng).clamp(min_length, max_length);
+            let b = a + random_normal_vec2(rng) * length;
+            if fits(&segments, a, b, body) {
+                placed = Some((a, b));
+                break;
+            }
+        }
+        let Some((a, b)) = placed else {
+            break;
+        };
+        segments.push(Segment {
+            a,
+            b,
+            t: SegmentType::ALL[types.sample(rng)],
+        });
+        points.push(b);
+    }
+    debug_assert!(
+        validate_body_plan(&segments, body).is_ok(),
+        "generated an invalid body plan: {:?}",
+        validate_body_plan(&segments, body)
+    );
+    segments
+}
+
+/// Whether a new segment from the existing point `a` to `b` can be added to the body.
+fn fits(segments: &[Segment], a: glam::Vec2, b: glam::Vec2, body: &BodyConfig) -> bool {
+    if !b.is_finite() || b.length() > body.max_radius || a.distance(b) < body.min_segment_length {
+        return false;
+    }
+    segments
+        .iter()
+        .all(|segment| !segments_clash(a, b, segment.a, segment.b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creature::SimRng;
+
+    /// Bodies generated from seeds 0 to 199.
+    fn bodies(generator: &GeneratorConfig, body: &BodyConfig) -> Vec<Vec<Segment>> {
+        (0..200)
+            .map(|seed| generate(generator, body, &mut SimRng::new(seed, 0)))
+            .collect()
+    }
+
+    #[test]
+    fn segment_counts_are_within_the_limits() {
+        let generator = GeneratorConfig::default();
+        for segments in bodies(&generator, &BodyConfig::default()) {
+            assert!((generator.min_segments..=generator.max_segments).contains(&segments.len()));
+        }
+        let body = BodyConfig {
+            max_segments: 3,
+            ..BodyConfig::default()
+        };
+        for segments in bodies(&generator, &body) {
+            assert!((generator.min_segments..=3).contains(&segments.len()));
+        }
+        // Even the minimum is capped by the body limits.
+        let generator = GeneratorConfig {
+            min_segments: 5,
+            ..generator
+        };
+        for segments in bodies(&generator, &body) {
+            assert_eq!(segments.len(), 3);
+        }
+    }
+
+    #[test]
+    fn bodies_are_valid() {
+        let body = BodyConfig::default();
+        // Long segments crowd the body, so many don't fit.
+        let crowded = GeneratorConfig {
+            min_segments: 20,
+            max_segments: 32,
+            mean_segment_length: 100.0,
+            ..GeneratorConfig::default()
+        };
+        for generator in [GeneratorConfig::default(), crowded] {
+            for segments in bodies(&generator, &body) {
+                assert_eq!(validate_body_plan(&segments, &body), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_body() {
+        let (generator, body) = (GeneratorConfig::default(), BodyConfig::default());
+        assert_eq!(bodies(&generator, &body), bodies(&generator, &body));
+    }
+
+    #[test]
+    fn types_with_zero_weight_never_appear() {
+        for t in SegmentType::ALL {
+            let mut generator = GeneratorConfig::default();
+            let weights = &mut generator.type_weights;
+            *match t {
+                SegmentType::Energy => &mut weights.energy,
+                SegmentType::Attack => &mut weights.attack,
+                SegmentType::Defend => &mut weights.defend,
+                SegmentType::Move => &mut weights.move_,
+            } = 0.0;
+            for segments in bodies(&generator, &BodyConfig::default()) {
+                assert!(segments.iter().all(|segment| segment.t != t), "{t:?}");
+            }
+        }
+    }
+}
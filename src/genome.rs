@@ -0,0 +1,176 @@
+//! Heritable description of a creature.
+//!
+//! Genomes are saved as JSON. Points are `[x, y]` pairs relative to the creature's position, and
+//! segment types are one of `"Energy"`, `"Attack"`, `"Defend"` or `"Move"`:
+//!
+//! ```json
+//! {
+//!   "segments": [
+//!     { "a": [0.0, 0.0], "b": [0.0, 30.0], "type": "Energy" },
+//!     { "a": [0.0, 30.0], "b": [30.0, 30.0], "type": "Move" }
+//!   ]
+//! }
+//! ```
+//!
+//! A genome file holds either a single genome, or a JSON array of genomes.
+
+use crate::{
+    body_plan::{validate_body_plan, BodyPlanError},
+    config::{BodyConfig, Config, MutationConfig},
+    creature::{Segment, SegmentType},
+    generator::generate,
+    mutation::mutate,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Genome {
+    pub segments: Vec<Segment>,
+}
+
+#[derive(Debug)]
+pub enum GenomeError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+}
+
+impl std::fmt::Display for GenomeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GenomeError::Io(error) => write!(f, "could not read or write genome: {}", error),
+            GenomeError::Json(error) => write!(f, "invalid genome: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for GenomeError {}
+
+impl From<std::io::Error> for GenomeError {
+    fn from(error: std::io::Error) -> Self {
+        GenomeError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for GenomeError {
+    fn from(error: serde_json::Error) -> Self {
+        GenomeError::Json(error)
+    }
+}
+
+impl Default for Genome {
+    fn default() -> Self {
+        let mut segments = Vec::with_capacity(15);
+        segments.push(Segment {
+            a: glam::Vec2::new(0.0, 0.0),
+            b: glam::Vec2::new(0.0, 30.0),
+            t: SegmentType::Energy,
+        });
+        segments.push(Segment {
+            a: glam::Vec2::new(0.0, 30.0),
+            b: glam::Vec2::new(30.0, 30.0),
+            t: SegmentType::Move,
+        });
+        segments.push(Segment {
+            a: glam::Vec2::new(30.0, 30.0),
+            b: glam::Vec2::new(30.0, 0.0),
+            t: SegmentType::Attack,
+        });
+        Self { segments }
+    }
+}
+
+impl Genome {
+    pub fn mutate(&mut self, config: &MutationConfig, rng: &mut impl Rng) {
+        mutate(&mut self.segments, config, rng);
+    }
+
+    /// A random valid body plan. See `generator::generate`.
+    pub fn random(config: &Config, rng: &mut impl Rng) -> Self {
+        Self {
+            segments: generate(&config.generator, &config.body, rng),
+        }
+    }
+
+    pub fn validate(&self, config: &BodyConfig) -> Result<(), BodyPlanError> {
+        validate_body_plan(&self.segments, config)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, GenomeError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), GenomeError> {
+        Ok(std::fs::write(path, self.to_json())?)
+    }
+
+    /// Saves several genomes to one file as a JSON array.
+    pub fn save_all(genomes: &[Genome], path: impl AsRef<Path>) -> Result<(), GenomeError> {
+        Ok(std::fs::write(
+            path,
+            serde_json::to_string_pretty(genomes).unwrap(),
+        )?)
+    }
+
+    /// Parses a single genome or a JSON array of genomes.
+    pub fn from_json_all(json: &str) -> Result<Vec<Self>, GenomeError> {
+        // Deciding up front, rather than trying one and then the other, lets errors point at the
+        // actual problem.
+        if json.trim_start().starts_with('[') {
+            Ok(serde_json::from_str(json)?)
+        } else {
+            Ok(vec![Self::from_json(json)?])
+        }
+    }
+
+    /// Loads every genome in a file. The file may hold a single genome or an array of them.
+    pub fn load_all(path: impl AsRef<Path>) -> Result<Vec<Self>, GenomeError> {
+        Self::from_json_all(&std::fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The example in the module documentation.
+    const EXAMPLE: &str = r#"{
+  "segments": [
+    { "a": [0.0, 0.0], "b": [0.0, 30.0], "type": "Energy" },
+    { "a": [0.0, 30.0], "b": [30.0, 30.0], "type": "Move" }
+  ]
+}"#;
+
+    #[test]
+    fn documented_example_parses() {
+        let genome = Genome::from_json(EXAMPLE).unwrap();
+        assert_eq!(genome.segments, Genome::default().segments[..2]);
+        assert_eq!(Genome::from_json(&genome.to_json()).unwrap(), genome);
+    }
+
+    #[test]
+    fn files_hold_one_genome_or_an_array() {
+        let genomes = vec![Genome::default(), Genome::from_json(EXAMPLE).unwrap()];
+        let array = serde_json::to_string_pretty(&genomes).unwrap();
+        assert_eq!(Genome::from_json_all(&array).unwrap(), genomes);
+        assert_eq!(
+            Genome::from_json_all(&genomes[0].to_json()).unwrap(),
+            genomes[..1]
+        );
+        assert_eq!(Genome::from_json_all(" []").unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn errors_name_the_bad_field() {
+        let misspelled = EXAMPLE.replacen("\"type\"", "\"typ\"", 1);
+        for json in [misspelled.clone(), format!("[{}]", misspelled)] {
+            let error = Genome::from_json_all(&json).unwrap_err().to_string();
+            assert!(error.contains("missing field `type`"), "{}", error);
+        }
+    }
+}
@@ -1,4 +1,23 @@
-mod creature;
+pub mod body_plan;
+pub mod boundary;
+pub mod collision;
+pub mod combat;
+pub mod config;
+pub mod corpse;
+pub mod creature;
+pub mod environment;
+pub mod fixed_timestep;
 pub mod fps_stats;
+pub mod generator;
+pub mod genome;
 pub mod life_sim;
+pub mod lineage;
+pub mod mutation;
+pub mod periodic;
 mod renderer;
+pub mod replay;
+pub mod snapshot;
+pub mod spatial;
+pub mod species;
+pub mod stats;
+pub mod world;
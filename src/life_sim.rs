@@ -1,8 +1,14 @@
 use crate::{
+    corpse::Corpse,
     creature::{Creature, SegmentType},
+    fixed_timestep::FixedTimestep,
     renderer::{Color, Line, Renderer},
+    world::World,
 };
 
+const CORPSE_BRIGHTNESS: f32 = 0.3;
+const BORDER_BRIGHTNESS: f32 = 0.2;
+
 impl From<SegmentType> for Color {
     fn from(segment_type: SegmentType) -> Self {
         match segment_type {
@@ -14,44 +20,137 @@ impl From<SegmentType> for Color {
     }
 }
 
-impl From<&Creature> for Vec<Line> {
-    fn from(creature: &Creature) -> Self {
-        creature
-            .segments
-            .iter()
-            .map(|segment| {
-                Line::new(
-                    creature.position + segment.a,
-                    creature.position + segment.b,
-                    segment.t.into(),
-                )
-            })
-            .collect()
-    }
+/// The creature's segments as lines, with the creature's position and angle interpolated by
+/// `alpha`.
+fn creature_lines(creature: &Creature, alpha: f32) -> Vec<Line> {
+    creature
+        .segments
+        .iter()
+        .map(|segment| {
+            let (a, b) = creature.interpolated_world_segment(segment, alpha);
+            Line::new(a, b, segment.t.into())
+        })
+        .collect()
+}
+
+/// Corpses are drawn as dim versions of the creatures they were.
+fn corpse_lines(corpse: &Corpse) -> Vec<Line> {
+    corpse
+        .segments
+        .iter()
+        .map(|segment| {
+            let Color(color) = segment.t.into();
+            Line::new(segment.a, segment.b, Color(color * CORPSE_BRIGHTNESS))
+        })
+        .collect()
+}
+
+/// An outline around the edge of the world.
+fn border_lines(world_size: glam::Vec2) -> Vec<Line> {
+    let corners = [
+        glam::Vec2::new(-world_size.x, -world_size.y),
+        glam::Vec2::new(world_size.x, -world_size.y),
+        glam::Vec2::new(world_size.x, world_size.y),
+        glam::Vec2::new(-world_size.x, world_size.y),
+    ];
+    (0..corners.len())
+        .map(|i| {
+            Line::new(
+                corners[i],
+                corners[(i + 1) % corners.len()],
+                Color(glam::Vec3::splat(BORDER_BRIGHTNESS)),
+            )
+        })
+        .collect()
 }
 
+fn shifted(lines: &[Line], offset: glam::Vec2) -> Vec<Line> {
+    lines.iter().map(|line| line.shifted(offset)).collect()
+}
+
+/// Drives a World at a fixed timestep, optionally drawing it to a window.
 pub struct LifeSim {
-    renderer: Renderer,
-    creature: Creature,
+    world: World,
+    timestep: FixedTimestep,
+    renderer: Option<Renderer>,
 }
 
 impl LifeSim {
-    pub fn new(window: winit::window::Window) -> Self {
+    /// A LifeSim that only simulates. No window or GPU is required.
+    pub fn headless(world: World) -> Self {
+        let timestep = Self::timestep(&world);
         Self {
-            renderer: Renderer::new(window),
-            creature: Creature::default(),
+            world,
+            timestep,
+            renderer: None,
         }
     }
 
+    /// A LifeSim that draws to the given window. The world is scaled to fit the window.
+    pub fn with_window(world: World, window: winit::window::Window) -> Self {
+        let timestep = Self::timestep(&world);
+        let renderer = Renderer::new(window, world.config.world.size);
+        let life_sim = Self {
+            world,
+            timestep,
+            renderer: Some(renderer),
+        };
+        life_sim.configure_surface();
+        life_sim
+    }
+
+    /// Steps at the world's `delta_time`, as many as its config allows per frame.
+    fn timestep(world: &World) -> FixedTimestep {
+        FixedTimestep::new(
+            world.config.world.delta_time as f32,
+            world.config.world.max_steps_per_frame,
+        )
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
     /// The underlying Renderer must be told when the window surface is resized.
     pub fn configure_surface(&self) {
-        self.renderer.configure_surface();
+        if let Some(renderer) = &self.renderer {
+            renderer.configure_surface();
+        }
     }
 
-    pub fn draw_creature(&mut self, delta_time: f32) {
-        self.creature.update(delta_time, self.renderer.world_size());
-        self.renderer
-            .draw_lines(&Into::<Vec<Line>>::into(&self.creature));
-        self.renderer.present();
+    /// Runs however many fixed simulation steps are due after `frame_time` seconds of real time.
+    pub fn update(&mut self, frame_time: f32) {
+        for _ in 0..self.timestep.advance(frame_time) {
+            self.world.update();
+        }
+    }
+
+    /// Draws the world if a window is attached, otherwise does nothing.
+    pub fn draw(&mut self) {
+        if let Some(renderer) = &mut self.renderer {
+            let world_size = self.world.config.world.size;
+            let boundary = self.world.config.world.boundary;
+            renderer.set_world_size(world_size);
+            renderer.draw_lines(&border_lines(world_size));
+            for corpse in self.world.corpses.iter() {
+                let lines = corpse_lines(corpse);
+                for offset in boundary.copies(corpse.center, corpse.radius, world_size) {
+                    renderer.draw_lines(&shifted(&lines, offset));
+                }
+            }
+            let alpha = self.timestep.alpha();
+            for creature in self.world.creatures.iter() {
+                let lines = creature_lines(creature, alpha);
+                let center = creature.interpolated_position(alpha);
+                for offset in boundary.copies(center, creature.radius(), world_size) {
+                    renderer.draw_lines(&shifted(&lines, offset));
+                }
+            }
+            renderer.present();
+        }
     }
 }
@@ -0,0 +1,245 @@
+//! Who descended from whom, and when they lived.
+
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LineageRecord {
+    pub id: u64,
+    /// None for creatures that were spawned rather than born.
+    pub parent_id: Option<u64>,
+    pub birth_time: f64,
+    /// None while the creature is alive.
+    pub death_time: Option<f64>,
+}
+
+/// Birth and death records of every creature that has lived in a World.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Lineage {
+    records: BTreeMap<u64, LineageRecord>,
+}
+
+/// Steps of the Newick writer. See `Lineage::to_newick`.
+enum Task {
+    /// Write the part of the tree starting with `id` at `start_time`, from its `next_child`th
+    /// child onwards.
+    Visit {
+        id: u64,
+        start_time: f64,
+        next_child: usize,
+    },
+    /// Join the two subtrees on top of the result stack into one branching at `split_time`.
+    Join { start_time: f64, split_time: f64 },
+}
+
+impl Lineage {
+    pub fn record_birth(&mut self, id: u64, parent_id: Option<u64>, time: f64) {
+        self.records.insert(
+            id,
+            LineageRecord {
+                id,
+                parent_id,
+                birth_time: time,
+                death_time: None,
+            },
+        );
+    }
+
+    pub fn record_death(&mut self, id: u64, time: f64) {
+        if let Some(record) = self.records.get_mut(&id) {
+            record.death_time = Some(time);
+        }
+    }
+
+    pub fn get(&self, id: u64) -> Option<&LineageRecord> {
+        self.records.get(&id)
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = &LineageRecord> {
+        self.records.values()
+    }
+
+    /// The phylogenetic tree in Newick format, with branch lengths in simulated seconds.
+    ///
+    /// Each birth splits the parent's branch in two: one side is the child, the other is the parent
+    /// carrying on. Leaves are labelled with creature IDs and end at their death, or at `end_time`
+    /// if still alive. Creatures spawned without a parent each root their own tree, and these are
+    /// joined at the top. If `prune` is set, branches with no living descendants are left out.
+    pub fn to_newick(&self, end_time: f64, prune: bool) -> String {
+        let mut children: BTreeMap<u64, Vec<(f64, u64)>> = BTreeMap::new();
+        let mut roots = Vec::new();
+        for record in self.records.values() {
+            match record.parent_id {
+                Some(parent_id) if self.records.contains_key(&parent_id) => children
+                    .entry(parent_id)
+                    .or_default()
+                    .push((record.birth_time, record.id)),
+                _ => roots.push(record.id),
+            }
+        }
+        for siblings in children.values_mut() {
+            siblings.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        }
+        // Subtrees as (text without branch length, branch length). None if pruned away.
+        let mut trees: Vec<(String, f64)> = Vec::new();
+        for root in roots {
+            let record = &self.records[&root];
+            // The tree can be as deep as the number of generations, so walk it with an explicit
+            // stack rather than recursion.
+            let mut tasks = vec![Task::Visit {
+                id: root,
+                start_time: record.birth_time,
+                next_child: 0,
+            }];
+            let mut results: Vec<Option<(String, f64)>> = Vec::new();
+            while let Some(task) = tasks.pop() {
+                match task {
+                    Task::Visit {
+                        id,
+                        start_time,
+                        next_child,
+                    } => match children.get(&id).and_then(|c| c.get(next_child)) {
+                        Some(&(split_time, child_id)) => {
+                            tasks.push(Task::Join {
+                                start_time,
+                                split_time,
+                            });
+                            tasks.push(Task::Visit {
+                                id,
+                                start_time: split_time,
+                                next_child: next_child + 1,
+                            });
+                            tasks.push(Task::Visit {
+                                id: child_id,
+                                start_time: split_time,
+                                next_child: 0,
+                            });
+                        }
+                        None => {
+                            let death_time = self.records[&id].death_time;
+                            if prune && death_time.is_some() {
+                                results.push(None);
+                            } else {
+                                let end = death_time.unwrap_or(end_time);
+                                results.push(Some((id.to_string(), end - start_time)));
+                            }
+                        }
+                    },
+                    Task::Join {
+                        start_time,
+                        split_time,
+                    } => {
+                        let parent = results.pop().unwrap();
+                        let child = results.pop().unwrap();
+                        let length = split_time - start_time;
+                        results.push(match (child, parent) {
+                            (Some(child), Some(parent)) => Some((
+                                format!("({}:{},{}:{})", child.0, child.1, parent.0, parent.1),
+                                length,
+                            )),
+                            (Some(only), None) | (None, Some(only)) => {
+                                Some((only.0, only.1 + length))
+                            }
+                            (None, None) => None,
+                        });
+                    }
+                }
+            }
+            if let Some(tree) = results.pop().unwrap() {
+                trees.push(tree);
+            }
+        }
+        let trees: Vec<String> = trees
+            .iter()
+            .map(|(text, length)| format!("{}:{}", text, length))
+            .collect();
+        match trees.len() {
+            0 => ";".to_string(),
+            1 => format!("{};", trees[0]),
+            _ => format!("({});", trees.join(",")),
+        }
+    }
+
+    pub fn save_newick(
+        &self,
+        path: impl AsRef<Path>,
+        end_time: f64,
+        prune: bool,
+    ) -> std::io::Result<()> {
+        std::fs::write(path, self.to_newick(end_time, prune) + "\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_creature() {
+        let mut lineage = Lineage::default();
+        lineage.record_birth(1, None, 0.0);
+        assert_eq!(lineage.to_newick(10.0, false), "1:10;");
+    }
+
+    #[test]
+    fn birth_splits_parent_branch() {
+        let mut lineage = Lineage::default();
+        lineage.record_birth(1, None, 0.0);
+        lineage.record_birth(2, Some(1), 4.0);
+        assert_eq!(lineage.to_newick(10.0, false), "(2:6,1:6):4;");
+    }
+
+    #[test]
+    fn spawned_creatures_are_joined_at_the_top() {
+        let mut lineage = Lineage::default();
+        lineage.record_birth(1, None, 0.0);
+        lineage.record_birth(2, None, 1.0);
+        // A parent missing from the records roots its own tree too.
+        lineage.record_birth(3, Some(99), 2.0);
+        assert_eq!(lineage.to_newick(3.0, false), "(1:3,2:2,3:1);");
+    }
+
+    fn family() -> Lineage {
+        let mut lineage = Lineage::default();
+        lineage.record_birth(1, None, 0.0);
+        lineage.record_birth(2, Some(1), 2.0);
+        lineage.record_birth(3, Some(1), 5.0);
+        lineage.record_death(2, 6.0);
+        lineage.record_death(1, 8.0);
+        lineage
+    }
+
+    #[test]
+    fn dead_branches_end_at_death() {
+        assert_eq!(family().to_newick(10.0, false), "(2:4,(3:5,1:3):3):2;");
+    }
+
+    #[test]
+    fn pruning_removes_dead_branches_and_merges_lengths() {
+        assert_eq!(family().to_newick(10.0, true), "3:10;");
+    }
+
+    #[test]
+    fn pruning_everything_leaves_an_empty_tree() {
+        let mut lineage = family();
+        lineage.record_death(3, 9.0);
+        assert_eq!(lineage.to_newick(10.0, true), ";");
+    }
+
+    #[test]
+    fn deep_lineage_does_not_overflow_the_stack() {
+        let generations = 100_000;
+        let mut lineage = Lineage::default();
+        lineage.record_birth(1, None, 0.0);
+        for id in 2..=generations {
+            lineage.record_birth(id, Some(id - 1), (id - 1) as f64);
+            lineage.record_death(id - 1, id as f64);
+        }
+        let end_time = generations as f64;
+        assert_eq!(
+            lineage.to_newick(end_time, true),
+            format!("{}:{};", generations, generations)
+        );
+        assert!(lineage.to_newick(end_time, false).ends_with(";"));
+    }
+}
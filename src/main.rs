@@ -1,53 +1,369 @@
-use life_sim::life_sim::LifeSim;
+use clap::Parser as _;
+use life_sim::{
+    config::Config,
+    genome::Genome,
+    life_sim::LifeSim,
+    periodic::{Periodic as _, Schedule},
+    snapshot::Snapshot,
+    species::SpeciesTracker,
+    stats::StatsCollector,
+    world::World,
+};
+use std::{error::Error, path::PathBuf, time::Instant};
 
-fn main() {
-    env_logger::init();
-    let event_loop = winit::event_loop::EventLoop::new().unwrap();
-    let window: winit::window::Window = winit::window::Window::new(&event_loop).unwrap();
-    let mut life_sim = LifeSim::new(window);
-    life_sim.configure_surface();
-    event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
-    let mut fps_stats = life_sim::fps_stats::FPSStats::new(1.0, 10.0);
-    event_loop
-        .run(move |event, event_loop_window_target| match event {
-            winit::event::Event::WindowEvent {
-                window_id: _,
-                event: window_event,
-            } => match window_event {
-                winit::event::WindowEvent::CloseRequested
-                | winit::event::WindowEvent::KeyboardInput {
-                    device_id: _,
-                    is_synthetic: _,
-                    event:
-                        winit::event::KeyEvent {
-                            physical_key: _,
-                            logical_key:
-                                winit::keyboard::Key::Named(winit::keyboard::NamedKey::Escape),
-                            text: _,
-                            location: _,
-                            state: _,
-                            repeat: _,
-                            ..
-                        },
-                } => {
-                    event_loop_window_target.exit();
+/// An artificial life simulation of creatures made of line segments.
+#[derive(clap::Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// Log more. Repeat for even more (-vv). RUST_LOG overrides this.
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Log less. Repeat for even less (-qq).
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    quiet: u8,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Simulate in a window. Press Escape or close the window to stop.
+    Run {
+        #[command(flatten)]
+        world: WorldArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Simulate without a window, as fast as possible, for a fixed number of ticks.
+    Headless {
+        #[command(flatten)]
+        world: WorldArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+        /// Number of fixed time steps to simulate.
+        #[arg(long, default_value_t = 12_000)]
+        ticks: u64,
+    },
+    /// Measure how many ticks per second the simulation runs at, without a window.
+    Benchmark {
+        #[command(flatten)]
+        world: WorldArgs,
+        /// Number of fixed time steps to simulate.
+        #[arg(long, default_value_t = 1_200)]
+        ticks: u64,
+    },
+}
+
+#[derive(clap::Args)]
+struct WorldArgs {
+    /// Random seed. A random seed is picked and logged if not given.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// TOML or JSON config file. Keys missing from the file take their defaults.
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// JSON file of one genome or an array of genomes to start from, instead of the default body
+    /// plan.
+    #[arg(long, value_name = "FILE")]
+    genomes: Option<PathBuf>,
+    /// Start from random body plans generated from the seed, as set up in the config's
+    /// `generator` section, instead of the default body plan.
+    #[arg(long, conflicts_with = "genomes")]
+    random_genomes: bool,
+    /// Number of creatures to start with. Genomes from --genomes are used in turn. Defaults to one
+    /// creature per genome, or the config's `world.initial_population` without --genomes.
+    #[arg(long)]
+    population: Option<usize>,
+    /// Snapshot to carry on from, such as the snapshot.json an earlier run wrote to its output
+    /// directory. The snapshot has its own seed, config and creatures.
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["seed", "config", "genomes", "random_genomes", "population"]
+    )]
+    resume: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct OutputArgs {
+    /// Directory to write the config, population stats, species, phylogeny and final snapshot to.
+    /// Created if missing.
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+    /// Seconds of simulated time between snapshots written to the output directory during the run,
+    /// so a run that is killed can be resumed. Without this, a snapshot is only written at the
+    /// end.
+    #[arg(long, value_name = "SECONDS", requires = "output_dir")]
+    snapshot_interval: Option<f64>,
+}
+
+impl WorldArgs {
+    /// Also returns the species tracked so far, when resuming a snapshot that has them.
+    fn build(&self) -> Result<(World, Option<SpeciesTracker>), Box<dyn Error>> {
+        if let Some(path) = &self.resume {
+            let mut snapshot =
+                Snapshot::load(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+            let species = snapshot.species.take();
+            let world = World::from_snapshot(snapshot)
+                .map_err(|error| format!("{}: {}", path.display(), error))?;
+            log::info!("Resuming at {:.1}s, seed {}", world.time(), world.seed);
+            return Ok((world, species));
+        }
+        let config = match &self.config {
+            Some(path) => {
+                Config::load(path).map_err(|error| format!("{}: {}", path.display(), error))?
+            }
+            None => Config::default(),
+        };
+        log::debug!("Config:\n{}", config.to_toml());
+        let genomes = match &self.genomes {
+            Some(path) => {
+                let genomes = Genome::load_all(path)
+                    .map_err(|error| format!("{}: {}", path.display(), error))?;
+                if genomes.is_empty() {
+                    return Err(format!("{}: the file has no genomes", path.display()).into());
                 }
-                winit::event::WindowEvent::Resized(_) => {
-                    life_sim.configure_surface();
+                for (i, genome) in genomes.iter().enumerate() {
+                    genome
+                        .validate(&config.body)
+                        .map_err(|error| format!("{}: genome {}: {}", path.display(), i, error))?;
                 }
-                _ => {}
-            },
-            winit::event::Event::AboutToWait => {
-                let tick = fps_stats.tick();
-                if tick.should_log {
-                    let fps = 1.0 / fps_stats.mean();
-                    let fps_std = fps_stats.std() / fps_stats.mean().powi(2);
-                    let fps_99th = 1.0 / fps_stats.percentile_99();
-                    log::info!("FPS: {:.0} ({:.0} ± {:.0})", fps_99th, fps, fps_std);
+                genomes
+            }
+            None => vec![Genome::default()],
+        };
+        let population = match (self.population, &self.genomes) {
+            (Some(population), _) => population,
+            (None, Some(_)) => genomes.len(),
+            (None, None) => config.world.initial_population,
+        };
+        let seed = self.seed.unwrap_or_else(rand::random);
+        log::info!("Seed: {}", seed);
+        if self.random_genomes {
+            return Ok((World::from_random_genomes(population, seed, config)?, None));
+        }
+        let genomes = genomes.into_iter().cycle().take(population);
+        Ok((World::from_genomes(genomes, seed, config)?, None))
+    }
+}
+
+/// Files written to the output directory of a run.
+struct Outputs {
+    dir: PathBuf,
+    stats: StatsCollector,
+    species: SpeciesTracker,
+    snapshots: Option<Schedule>,
+}
+
+impl Outputs {
+    /// Creates the output directory, if one was asked for, and echoes the config into it. Species
+    /// carry on from `species` if given.
+    fn new(
+        args: &OutputArgs,
+        world: &World,
+        species: Option<SpeciesTracker>,
+    ) -> Result<Option<Self>, Box<dyn Error>> {
+        let Some(dir) = &args.output_dir else {
+            return Ok(None);
+        };
+        let snapshots = match args.snapshot_interval {
+            Some(interval) if !(interval > 0.0 && interval.is_finite()) => {
+                return Err("--snapshot-interval must be positive".into());
+            }
+            Some(interval) => Some(Schedule::new(interval, world.time())),
+            None => None,
+        };
+        std::fs::create_dir_all(dir)?;
+        world.config.save(dir.join("config.toml"))?;
+        Ok(Some(Self {
+            dir: dir.clone(),
+            stats: StatsCollector::new(world),
+            species: species.unwrap_or_else(|| SpeciesTracker::new(world)),
+            snapshots,
+        }))
+    }
+
+    fn observe(&mut self, world: &World) -> Result<(), Box<dyn Error>> {
+        self.stats.observe(world);
+        self.species.observe(world);
+        if let Some(schedule) = &mut self.snapshots {
+            if schedule.is_due(world.time()) {
+                self.save_snapshot(world)?;
+                log::debug!("Wrote snapshot at {:.1}s", world.time());
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes to a temporary file first, so a run killed while writing keeps its last snapshot.
+    fn save_snapshot(&self, world: &World) -> Result<(), Box<dyn Error>> {
+        let mut snapshot = world.snapshot();
+        snapshot.species = Some(self.species.clone());
+        let path = self.dir.join("snapshot.json");
+        let temporary_path = self.dir.join("snapshot.json.tmp");
+        snapshot.save(&temporary_path)?;
+        std::fs::rename(temporary_path, path)?;
+        Ok(())
+    }
+
+    fn finish(&self, world: &World) -> Result<(), Box<dyn Error>> {
+        self.stats.save_csv(self.dir.join("stats.csv"))?;
+        self.species.save_csv(self.dir.join("species.csv"))?;
+        self.species.save_species(self.dir.join("species.json"))?;
+        world
+            .lineage
+            .save_newick(self.dir.join("phylogeny.nwk"), world.time(), true)?;
+        self.save_snapshot(world)?;
+        log::info!("Wrote outputs to {}", self.dir.display());
+        Ok(())
+    }
+}
+
+fn init_logging(verbose: u8, quiet: u8) {
+    let level = match verbose as i16 - quiet as i16 {
+        i16::MIN..=-3 => log::LevelFilter::Off,
+        -2 => log::LevelFilter::Error,
+        -1 => log::LevelFilter::Warn,
+        0 => log::LevelFilter::Info,
+        1 => log::LevelFilter::Debug,
+        2.. => log::LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(log::LevelFilter::Warn.min(level))
+        .filter_module("life_sim", level)
+        .parse_default_env()
+        .init();
+}
+
+fn main() {
+    let cli = Cli::parse();
+    init_logging(cli.verbose, cli.quiet);
+    let result = match cli.command {
+        Command::Run { world, output } => run(&world, &output),
+        Command::Headless {
+            world,
+            output,
+            ticks,
+        } => headless(&world, &output, ticks),
+        Command::Benchmark { world, ticks } => benchmark(&world, ticks),
+    };
+    if let Err(error) = result {
+        log::error!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+fn run(world_args: &WorldArgs, output_args: &OutputArgs) -> Result<(), Box<dyn Error>> {
+    let (world, species) = world_args.build()?;
+    let mut outputs = Outputs::new(output_args, &world, species)?;
+    let event_loop = winit::event_loop::EventLoop::new()?;
+    let window: winit::window::Window = winit::window::Window::new(&event_loop)?;
+    let fps_stats_config = world.config.fps_stats.clone();
+    let mut life_sim = LifeSim::with_window(world, window);
+    event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
+    let mut fps_stats = life_sim::fps_stats::FPSStats::new(
+        fps_stats_config.half_life,
+        fps_stats_config.log_frequency,
+    );
+    event_loop.run(move |event, event_loop_window_target| match event {
+        winit::event::Event::WindowEvent {
+            window_id: _,
+            event: window_event,
+        } => match window_event {
+            winit::event::WindowEvent::CloseRequested
+            | winit::event::WindowEvent::KeyboardInput {
+                device_id: _,
+                is_synthetic: _,
+                event:
+                    winit::event::KeyEvent {
+                        physical_key: _,
+                        logical_key: winit::keyboard::Key::Named(winit::keyboard::NamedKey::Escape),
+                        text: _,
+                        location: _,
+                        state: _,
+                        repeat: _,
+                        ..
+                    },
+            } => {
+                if let Some(outputs) = outputs.take() {
+                    if let Err(error) = outputs.finish(life_sim.world()) {
+                        log::error!("{}", error);
+                    }
                 }
-                life_sim.draw_creature(tick.frame_time);
+                event_loop_window_target.exit();
+            }
+            winit::event::WindowEvent::Resized(_) => {
+                life_sim.configure_surface();
             }
             _ => {}
-        })
-        .unwrap();
+        },
+        winit::event::Event::AboutToWait => {
+            let tick = fps_stats.tick();
+            if tick.should_log {
+                let fps = 1.0 / fps_stats.mean();
+                let fps_std = fps_stats.std() / fps_stats.mean().powi(2);
+                let fps_99th = 1.0 / fps_stats.percentile_99();
+                log::info!("FPS: {:.0} ({:.0} ± {:.0})", fps_99th, fps, fps_std);
+            }
+            life_sim.update(tick.frame_time);
+            if let Some(outputs) = &mut outputs {
+                if let Err(error) = outputs.observe(life_sim.world()) {
+                    log::error!("{}", error);
+                }
+            }
+            life_sim.draw();
+        }
+        _ => {}
+    })?;
+    Ok(())
+}
+
+fn headless(
+    world_args: &WorldArgs,
+    output_args: &OutputArgs,
+    ticks: u64,
+) -> Result<(), Box<dyn Error>> {
+    let (mut world, species) = world_args.build()?;
+    let mut outputs = Outputs::new(output_args, &world, species)?;
+    let log_interval = (ticks / 10).max(1);
+    for tick in 1..=ticks {
+        world.update();
+        if let Some(outputs) = &mut outputs {
+            outputs.observe(&world)?;
+        }
+        if tick % log_interval == 0 {
+            log::info!(
+                "Tick {}/{}: time {:.1}s, population {}, births {}, deaths {}",
+                tick,
+                ticks,
+                world.time(),
+                world.creatures.len(),
+                world.births,
+                world.deaths.total(),
+            );
+        }
+    }
+    if let Some(outputs) = &outputs {
+        outputs.finish(&world)?;
+    }
+    Ok(())
+}
+
+fn benchmark(world_args: &WorldArgs, ticks: u64) -> Result<(), Box<dyn Error>> {
+    let (mut world, _) = world_args.build()?;
+    let delta_time = world.config.world.delta_time;
+    let start = Instant::now();
+    for _ in 0..ticks {
+        world.update();
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    println!(
+        "{} ticks in {:.3}s: {:.1} ticks/s, {:.1}x real time, final population {}",
+        ticks,
+        elapsed,
+        ticks as f64 / elapsed,
+        ticks as f64 * delta_time / elapsed,
+        world.creatures.len(),
+    );
+    Ok(())
 }
@@ -0,0 +1,142 @@
+use crate::{
+    config::MutationConfig,
+    creature::{random_normal_vec2, Segment, SegmentType},
+};
+use rand::{seq::SliceRandom as _, Rng};
+
+/// Randomly changes a body plan. Segments sharing an endpoint keep sharing it, so a connected body
+/// stays connected.
+pub fn mutate(segments: &mut Vec<Segment>, config: &MutationConfig, rng: &mut impl Rng) {
+    jitter_points(segments, config, rng);
+    for segment in segments.iter_mut() {
+        if rng.gen::<f32>() < config.type_change_chance {
+            segment.t = *SegmentType::ALL.choose(rng).unwrap();
+        }
+    }
+    if rng.gen::<f32>() < config.insert_chance {
+        insert_segment(segments, rng);
+    }
+    if rng.gen::<f32>() < config.delete_chance {
+        delete_dangling_segment(segments, rng);
+    }
+}
+
+fn points(segments: &[Segment]) -> Vec<glam::Vec2> {
+    let mut points: Vec<glam::Vec2> = Vec::with_capacity(segments.len() * 2);
+    for segment in segments.iter() {
+        for point in [segment.a, segment.b] {
+            if !points.contains(&point) {
+                points.push(point);
+            }
+        }
+    }
+    points
+}
+
+fn jitter_points(segments: &mut [Segment], config: &MutationConfig, rng: &mut impl Rng) {
+    for point in points(segments) {
+        if rng.gen::<f32>() >= config.jitter_chance {
+            continue;
+        }
+        let distance = rng.gen::<f32>() * 2.0 * config.jitter_distance;
+        let new_point = point + random_normal_vec2(rng) * distance;
+        for segment in segments.iter_mut() {
+            if segment.a == point {
+                segment.a = new_point;
+            }
+            if segment.b == point {
+                segment.b = new_point;
+            }
+        }
+    }
+}
+
+fn insert_segment(segments: &mut Vec<Segment>, rng: &mut impl Rng) {
+    let Some(&a) = points(segments).choose(rng) else {
+        return;
+    };
+    let mean_length =
+        segments.iter().map(|segment| segment.length()).sum::<f32>() / segments.len() as f32;
+    let b = a + random_normal_vec2(rng) * mean_length;
+    segments.push(Segment {
+        a,
+        b,
+        t: *SegmentType::ALL.choose(rng).unwrap(),
+    });
+}
+
+/// Removes a segment with an endpoint not shared by any other segment. Removing such a segment
+/// cannot disconnect the body.
+fn delete_dangling_segment(segments: &mut Vec<Segment>, rng: &mut impl Rng) {
+    if segments.len() <= 1 {
+        return;
+    }
+    let shared = |point: glam::Vec2, index: usize| {
+        segments
+            .iter()
+            .enumerate()
+            .any(|(i, segment)| i != index && (segment.a == point || segment.b == point))
+    };
+    let dangling: Vec<usize> = (0..segments.len())
+        .filter(|&i| !shared(segments[i].a, i) || !shared(segments[i].b, i))
+        .collect();
+    if let Some(&index) = dangling.choose(rng) {
+        segments.remove(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{body_plan::count_pieces, config::Config, creature::SimRng, genome::Genome};
+
+    fn random_bodies(seed: u64) -> Vec<Vec<Segment>> {
+        let mut rng = SimRng::new(seed, 0);
+        (0..100)
+            .map(|_| Genome::random(&Config::default(), &mut rng).segments)
+            .collect()
+    }
+
+    /// For every pair of endpoints, whether they are the same point.
+    fn shared_endpoints(segments: &[Segment]) -> Vec<bool> {
+        let endpoints: Vec<glam::Vec2> = segments.iter().flat_map(|s| [s.a, s.b]).collect();
+        let mut shared = Vec::new();
+        for (i, a) in endpoints.iter().enumerate() {
+            for b in endpoints[i + 1..].iter() {
+                shared.push(a == b);
+            }
+        }
+        shared
+    }
+
+    #[test]
+    fn jitter_keeps_shared_endpoints_shared() {
+        let config = MutationConfig {
+            jitter_chance: 1.0,
+            ..MutationConfig::default()
+        };
+        let mut rng = SimRng::new(1, 1);
+        for mut segments in random_bodies(1) {
+            let before = segments.clone();
+            jitter_points(&mut segments, &config, &mut rng);
+            assert_ne!(segments, before);
+            assert_eq!(shared_endpoints(&segments), shared_endpoints(&before));
+        }
+    }
+
+    #[test]
+    fn deleting_dangling_segments_keeps_bodies_connected() {
+        let mut rng = SimRng::new(2, 1);
+        for mut segments in random_bodies(2) {
+            while segments.len() > 1 {
+                let count = segments.len();
+                delete_dangling_segment(&mut segments, &mut rng);
+                assert_eq!(count_pieces(&segments), 1);
+                if segments.len() == count {
+                    // Every segment is part of a loop.
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Looking at a running World at regular intervals of simulated time.
+
+use crate::world::World;
+use serde::{Deserialize, Serialize};
+
+/// When something that happens every `interval` seconds of simulated time is next due.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    /// Seconds between passes
+    interval: f64,
+    next_time: f64,
+}
+
+impl Schedule {
+    /// The first pass is due one interval after `start_time`, so passes fall on whole multiples
+    /// of the interval from there.
+    pub fn new(interval: f64, start_time: f64) -> Self {
+        assert!(interval > 0.0);
+        Self {
+            interval,
+            next_time: start_time + interval,
+        }
+    }
+
+    /// Whether a pass is due at `time`. If it is, the next one is scheduled.
+    pub fn is_due(&mut self, time: f64) -> bool {
+        if time < self.next_time {
+            return false;
+        }
+        self.next_time += self.interval;
+        true
+    }
+}
+
+/// Something that looks at a World on a Schedule, such as a StatsCollector.
+pub trait Periodic {
+    fn schedule(&mut self) -> &mut Schedule;
+
+    /// Looks at the world. Called by `observe` whenever a pass is due.
+    fn pass(&mut self, world: &World);
+
+    /// Call after every update. Runs a pass if one is due.
+    fn observe(&mut self, world: &World) {
+        if self.schedule().is_due(world.time()) {
+            self.pass(world);
+        }
+    }
+}
@@ -16,6 +16,14 @@ impl Line {
         let color = glam::Vec4::new(color.0.x, color.0.y, color.0.z, 1.0);
         Self { a, b, color }
     }
+
+    pub fn shifted(&self, offset: glam::Vec2) -> Self {
+        Self {
+            a: self.a + offset,
+            b: self.b + offset,
+            color: self.color,
+        }
+    }
 }
 
 fn empty_command_buffer(
@@ -48,12 +56,27 @@ fn empty_command_buffer(
     command_encoder.finish()
 }
 
+/// Half the width and height of the world space area to show, so that the whole world fits in the
+/// texture without being stretched.
+fn fit_view(world_size: glam::Vec2, texture_width: f32, texture_height: f32) -> glam::Vec2 {
+    let texture_aspect = texture_width / texture_height;
+    if world_size.x / world_size.y > texture_aspect {
+        glam::Vec2::new(world_size.x, world_size.x / texture_aspect)
+    } else {
+        glam::Vec2::new(world_size.y * texture_aspect, world_size.y)
+    }
+}
+
 /// Encodes commands to draw the given lines to the given texture. Returns a CommandBuffer.
+///
+/// The world spans from `-world_size` to `world_size` in world space, and is scaled to fit the
+/// texture.
 fn draw_lines(
     device: &wgpu::Device,
     preferred_texture_format: wgpu::TextureFormat,
     lines: &[Line],
     texture: &wgpu::Texture,
+    world_size: glam::Vec2,
 ) -> wgpu::CommandBuffer {
     let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
     if lines.is_empty() {
@@ -107,20 +130,19 @@ fn draw_lines(
         }),
         multiview: None,
     });
-    let world_space_size =
-        glam::Vec2::new(texture.width() as f32 / 2.0, texture.height() as f32 / 2.0);
-    let world_space_size_bytes: &[u8] = bytemuck::bytes_of(&world_space_size);
-    let world_space_size_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-        label: Some("line world space size buffer"),
-        size: 32.max(world_space_size_bytes.len() as u64),
+    let view_size = fit_view(world_size, texture.width() as f32, texture.height() as f32);
+    let view_size_bytes: &[u8] = bytemuck::bytes_of(&view_size);
+    let view_size_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("line view size buffer"),
+        size: 32.max(view_size_bytes.len() as u64),
         usage: wgpu::BufferUsages::UNIFORM,
         mapped_at_creation: true,
     });
-    world_space_size_buffer
-        .slice(0..world_space_size_bytes.len() as u64)
+    view_size_buffer
+        .slice(0..view_size_bytes.len() as u64)
         .get_mapped_range_mut()
-        .copy_from_slice(world_space_size_bytes);
-    world_space_size_buffer.unmap();
+        .copy_from_slice(view_size_bytes);
+    view_size_buffer.unmap();
     let line_bytes: &[u8] = bytemuck::cast_slice(lines);
     let line_storage_buffer = device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("line storage buffer"),
@@ -140,7 +162,7 @@ fn draw_lines(
             wgpu::BindGroupEntry {
                 binding: 0,
                 resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                    buffer: &world_space_size_buffer,
+                    buffer: &view_size_buffer,
                     offset: 0,
                     size: None,
                 }),
@@ -186,6 +208,8 @@ fn draw_lines(
 
 pub struct Renderer {
     lines: Vec<Line>,
+    /// Half the width and height of the world being drawn, in world units.
+    world_size: glam::Vec2,
     // WGPU Stuff
     device: wgpu::Device,
     queue: wgpu::Queue,
@@ -196,7 +220,7 @@ pub struct Renderer {
 }
 
 impl Renderer {
-    pub fn new(window: winit::window::Window) -> Self {
+    pub fn new(window: winit::window::Window, world_size: glam::Vec2) -> Self {
         let instance: wgpu::Instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
         // Safety: The window must live longer than its surface.
         let surface: wgpu::Surface = unsafe { instance.create_surface(&window) }.unwrap();
@@ -209,10 +233,11 @@ impl Renderer {
             .block_on()
             .unwrap();
         let preferred_texture_format: wgpu::TextureFormat =
-            *surface.get_capabilities(&adapter).formats.get(0).unwrap();
+            *surface.get_capabilities(&adapter).formats.first().unwrap();
         log::debug!("Preferred texture format: {:?}", &preferred_texture_format);
         Self {
             lines: Vec::with_capacity(1000),
+            world_size,
             device,
             queue,
             surface,
@@ -239,16 +264,9 @@ impl Renderer {
         );
     }
 
-    pub fn world_size(&self) -> glam::Vec2 {
-        let window_size = self.window.inner_size();
-        glam::Vec2::new(
-            window_size.width as f32 / 2.0,
-            window_size.height as f32 / 2.0,
-        )
-    }
-
-    pub fn draw_line(&mut self, line: Line) {
-        self.lines.push(line);
+    /// The world is scaled to fit the window, keeping its aspect ratio.
+    pub fn set_world_size(&mut self, world_size: glam::Vec2) {
+        self.world_size = world_size;
     }
 
     pub fn draw_lines(&mut self, lines: &[Line]) {
@@ -262,6 +280,7 @@ impl Renderer {
             self.preferred_texture_format,
             &self.lines,
             &surface_texture.texture,
+            self.world_size,
         );
         self.queue.submit([draw_lines_command_buffer]);
         surface_texture.present();
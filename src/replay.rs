@@ -0,0 +1,369 @@
+//! Recording runs so they can be replayed exactly.
+//!
+//! A recording is the world's initial snapshot (which includes the seed and the config, and so the
+//! time step) and every intervention made from outside the simulation along with the tick it was
+//! made on. Checksums of the world are recorded periodically, so a replay can verify that it is
+//! reproducing the original run and report the first tick where it is not.
+
+use crate::{
+    body_plan::BodyPlanError,
+    creature::{DeathCause, SimRng},
+    genome::Genome,
+    snapshot::{Snapshot, SnapshotError},
+    world::World,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A change made to the world from outside the simulation, such as by a user.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Intervention {
+    Spawn {
+        genome: Genome,
+        position: glam::Vec2,
+    },
+    Kill {
+        id: u64,
+    },
+}
+
+impl Intervention {
+    /// Fails, leaving the world unchanged, if a spawned body breaks the world's limits.
+    pub fn apply(&self, world: &mut World) -> Result<(), BodyPlanError> {
+        match self {
+            Intervention::Spawn { genome, position } => {
+                world.spawn(genome.clone(), *position)?;
+            }
+            Intervention::Kill { id } => {
+                if let Some(creature) = world.creatures.iter_mut().find(|c| c.id == *id) {
+                    creature.kill(DeathCause::Intervention);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Recording {
+    pub initial: Snapshot,
+    /// Ticks between checksums.
+    pub checksum_interval: u64,
+    /// Interventions and the tick they were applied before.
+    pub interventions: Vec<(u64, Intervention)>,
+    /// Checksums and the tick they were taken after.
+    pub checksums: Vec<(u64, u64)>,
+    /// How many ticks the recording runs for.
+    pub ticks: u64,
+}
+
+/// The first checksum in a replay that didn't match the recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    pub tick: u64,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+/// Why a replay stopped before the end of its recording.
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The initial snapshot could not be resumed.
+    Snapshot(SnapshotError),
+    /// A recorded intervention could not be applied before `tick`.
+    Intervention {
+        tick: u64,
+        error: BodyPlanError,
+    },
+    Divergence(Divergence),
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Snapshot(error) => write!(f, "{}", error),
+            ReplayError::Intervention { tick, error } => {
+                write!(f, "intervention before tick {} failed: {}", tick, error)
+            }
+            ReplayError::Divergence(divergence) => write!(
+                f,
+                "replay diverged at tick {}: expected checksum {:016x}, got {:016x}",
+                divergence.tick, divergence.expected, divergence.actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<SnapshotError> for ReplayError {
+    fn from(error: SnapshotError) -> Self {
+        ReplayError::Snapshot(error)
+    }
+}
+
+impl From<Divergence> for ReplayError {
+    fn from(divergence: Divergence) -> Self {
+        ReplayError::Divergence(divergence)
+    }
+}
+
+/// 64 bit FNV-1a hash.
+struct Hasher(u64);
+
+impl Hasher {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    fn add_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn add(&mut self, value: u64) {
+        self.add_bytes(&value.to_le_bytes());
+    }
+
+    fn add_f32(&mut self, value: f32) {
+        self.add(value.to_bits() as u64);
+    }
+
+    fn add_vec2(&mut self, value: glam::Vec2) {
+        self.add_f32(value.x);
+        self.add_f32(value.y);
+    }
+
+    /// The generator's state is private, so it is hashed in its serialized form.
+    fn add_rng(&mut self, rng: &SimRng) {
+        self.add_bytes(&serde_json::to_vec(rng).unwrap());
+    }
+}
+
+/// A checksum of everything that affects how the world evolves, including the state of every
+/// random stream. It only matches across machines that compute floating point functions such as
+/// `exp` and `ln` identically, so recordings are best checked on the platform they were made on.
+pub fn checksum(world: &World) -> u64 {
+    let mut hasher = Hasher::new();
+    hasher.add(world.ticks);
+    hasher.add(world.next_id());
+    hasher.add_rng(world.rng());
+    hasher.add(world.creatures.len() as u64);
+    for creature in world.creatures.iter() {
+        hasher.add(creature.id);
+        hasher.add_rng(&creature.rng);
+        hasher.add_vec2(creature.position);
+        hasher.add_vec2(creature.momentum);
+        hasher.add_f32(creature.angle);
+        hasher.add_f32(creature.angular_momentum);
+        hasher.add_f32(creature.energy);
+        hasher.add(creature.dead as u64);
+        for segment in creature.segments.iter() {
+            hasher.add_vec2(segment.a);
+            hasher.add_vec2(segment.b);
+            hasher.add(segment.t as u64);
+        }
+    }
+    hasher.add(world.corpses.len() as u64);
+    for corpse in world.corpses.iter() {
+        hasher.add_vec2(corpse.center);
+        hasher.add_f32(corpse.energy);
+    }
+    hasher.0
+}
+
+/// Steps a world while recording everything needed to replay it.
+pub struct Recorder {
+    recording: Recording,
+}
+
+impl Recorder {
+    /// Starts recording from the world's current state.
+    pub fn new(world: &World, checksum_interval: u64) -> Self {
+        assert!(checksum_interval > 0);
+        Self {
+            recording: Recording {
+                initial: world.snapshot(),
+                checksum_interval,
+                interventions: Vec::new(),
+                checksums: Vec::new(),
+                ticks: 0,
+            },
+        }
+    }
+
+    /// Applies the intervention and records it, to be applied before the next tick on replay.
+    /// Interventions that fail are not recorded.
+    pub fn intervene(
+        &mut self,
+        world: &mut World,
+        intervention: Intervention,
+    ) -> Result<(), BodyPlanError> {
+        intervention.apply(world)?;
+        self.recording
+            .interventions
+            .push((self.recording.ticks, intervention));
+        Ok(())
+    }
+
+    pub fn update(&mut self, world: &mut World) {
+        world.update();
+        self.recording.ticks += 1;
+        if self
+            .recording
+            .ticks
+            .is_multiple_of(self.recording.checksum_interval)
+        {
+            self.recording
+                .checksums
+                .push((self.recording.ticks, checksum(world)));
+        }
+    }
+
+    pub fn finish(self) -> Recording {
+        self.recording
+    }
+}
+
+impl Recording {
+    /// Replays the recording from the start. Returns the final world, or the first tick where the
+    /// replay's checksum didn't match the recording.
+    pub fn replay(&self) -> Result<World, ReplayError> {
+        let mut world = World::from_snapshot(self.initial.clone())?;
+        let mut interventions = self.interventions.iter().peekable();
+        let mut checksums = self.checksums.iter().peekable();
+        for tick in 0..self.ticks {
+            while let Some((_, intervention)) =
+                interventions.next_if(|(intervention_tick, _)| *intervention_tick == tick)
+            {
+                intervention
+                    .apply(&mut world)
+                    .map_err(|error| ReplayError::Intervention { tick, error })?;
+            }
+            world.update();
+            if let Some(&(_, expected)) =
+                checksums.next_if(|(checksum_tick, _)| *checksum_tick == tick + 1)
+            {
+                let actual = checksum(&world);
+                if actual != expected {
+                    return Err(Divergence {
+                        tick: tick + 1,
+                        expected,
+                        actual,
+                    }
+                    .into());
+                }
+            }
+        }
+        Ok(world)
+    }
+
+    /// Recordings are saved as JSON, with the initial state in snapshot format.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        Ok(std::fs::write(path, serde_json::to_string(self)?)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::tests::world;
+
+    fn recording() -> (Recording, u64) {
+        let mut world = world(7);
+        let mut recorder = Recorder::new(&world, 10);
+        for tick in 0..100 {
+            if tick == 30 {
+                let intervention = Intervention::Spawn {
+                    genome: Genome::default(),
+                    position: glam::Vec2::ZERO,
+                };
+                recorder.intervene(&mut world, intervention).unwrap();
+            }
+            recorder.update(&mut world);
+        }
+        (recorder.finish(), checksum(&world))
+    }
+
+    #[test]
+    fn replay_reproduces_recording() {
+        let (recording, final_checksum) = recording();
+        let world = recording.replay().unwrap();
+        assert_eq!(checksum(&world), final_checksum);
+    }
+
+    #[test]
+    fn replay_reports_divergence() {
+        let (mut recording, _) = recording();
+        recording.initial.creatures[0].energy += 1.0;
+        match recording.replay() {
+            Err(ReplayError::Divergence(divergence)) => {
+                assert_eq!(divergence.tick, 10);
+                assert_ne!(divergence.expected, divergence.actual);
+            }
+            result => panic!("expected a divergence, got {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn replay_reports_missed_intervention() {
+        let (mut recording, _) = recording();
+        recording.interventions.clear();
+        match recording.replay() {
+            Err(ReplayError::Divergence(divergence)) => assert_eq!(divergence.tick, 40),
+            result => panic!("expected a divergence, got {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn replay_survives_saving() {
+        let (recording, final_checksum) = recording();
+        let json = serde_json::to_string(&recording).unwrap();
+        let loaded: Recording = serde_json::from_str(&json).unwrap();
+        assert_eq!(checksum(&loaded.replay().unwrap()), final_checksum);
+    }
+
+    #[test]
+    fn failed_interventions_are_not_recorded() {
+        let mut world = world(7);
+        let mut recorder = Recorder::new(&world, 10);
+        let intervention = Intervention::Spawn {
+            genome: Genome {
+                segments: Vec::new(),
+            },
+            position: glam::Vec2::ZERO,
+        };
+        assert_eq!(
+            recorder.intervene(&mut world, intervention),
+            Err(BodyPlanError::Empty)
+        );
+        assert!(recorder.finish().interventions.is_empty());
+    }
+
+    #[test]
+    fn checksum_covers_random_streams() {
+        use rand::RngCore as _;
+        let world = world(7);
+        let mut creature_rng = World::from_snapshot(world.snapshot()).unwrap();
+        creature_rng.creatures[0].rng.next_u32();
+        assert_ne!(checksum(&creature_rng), checksum(&world));
+        let mut snapshot = world.snapshot();
+        snapshot.rng.next_u32();
+        assert_ne!(
+            checksum(&World::from_snapshot(snapshot).unwrap()),
+            checksum(&world)
+        );
+        let mut snapshot = world.snapshot();
+        snapshot.next_id += 1;
+        assert_ne!(
+            checksum(&World::from_snapshot(snapshot).unwrap()),
+            checksum(&world)
+        );
+    }
+}
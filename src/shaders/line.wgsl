@@ -9,7 +9,7 @@ struct VertexOut {
     @location(0) color: vec4f,
 }
 
-@group(0) @binding(0) var<uniform> world_space_size: vec2f;
+@group(0) @binding(0) var<uniform> view_size: vec2f;
 @group(0) @binding(1) var<storage> lines: array<Line>;
 
 @vertex
@@ -24,7 +24,7 @@ fn vertex_main(
     } else {
 	world_space_vertex = line.b;
     }
-    let normalized_vertex: vec2f = world_space_vertex / world_space_size;
+    let normalized_vertex: vec2f = world_space_vertex / view_size;
     return VertexOut(vec4f(normalized_vertex, 0.0, 1.0), line.color);
 }
 
@@ -0,0 +1,154 @@
+//! Saving and loading the full state of a World.
+//!
+//! Snapshots are JSON. Every snapshot records the `version` of the format it was written with.
+//! When the data model grows, bump `SNAPSHOT_VERSION` and upgrade older snapshots in `from_json`,
+//! so that they stay loadable.
+
+use crate::{
+    body_plan::BodyPlanError,
+    config::{Config, ConfigError},
+    corpse::Corpse,
+    creature::{Creature, SimRng},
+    lineage::Lineage,
+    species::SpeciesTracker,
+    stats::DeathCounts,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// The snapshot format version written by this build.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Everything needed to resume a World exactly where it left off.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub seed: u64,
+    /// Updates since the world was created.
+    pub ticks: u64,
+    pub births: u64,
+    pub deaths: DeathCounts,
+    pub lineage: Lineage,
+    pub config: Config,
+    pub next_id: u64,
+    pub rng: SimRng,
+    pub creatures: Vec<Creature>,
+    pub corpses: Vec<Corpse>,
+    /// Species are tracked outside the World, so whoever tracks them fills this in.
+    #[serde(default)]
+    pub species: Option<SpeciesTracker>,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// The snapshot was written by a newer build.
+    UnsupportedVersion(u32),
+    Config(ConfigError),
+    /// A creature's body breaks the limits in the snapshot's config.
+    InvalidCreature {
+        id: u64,
+        error: BodyPlanError,
+    },
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::Io(error) => write!(f, "could not read or write snapshot: {}", error),
+            SnapshotError::Json(error) => write!(f, "invalid snapshot: {}", error),
+            SnapshotError::UnsupportedVersion(version) => write!(
+                f,
+                "snapshot version {} is newer than the supported version {}",
+                version, SNAPSHOT_VERSION
+            ),
+            SnapshotError::Config(error) => write!(f, "invalid snapshot: {}", error),
+            SnapshotError::InvalidCreature { id, error } => {
+                write!(f, "invalid snapshot: creature {}: {}", id, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(error: std::io::Error) -> Self {
+        SnapshotError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(error: serde_json::Error) -> Self {
+        SnapshotError::Json(error)
+    }
+}
+
+impl From<ConfigError> for SnapshotError {
+    fn from(error: ConfigError) -> Self {
+        SnapshotError::Config(error)
+    }
+}
+
+impl Snapshot {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SnapshotError> {
+        let json: serde_json::Value = serde_json::from_str(json)?;
+        let version = json["version"].as_u64().unwrap_or(0) as u32;
+        if version > SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        // Snapshots older than SNAPSHOT_VERSION are upgraded here, one version at a time, before
+        // being deserialized.
+        Ok(serde_json::from_value(json)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        Ok(std::fs::write(path, self.to_json())?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        replay::checksum,
+        world::{
+            tests::{run, world},
+            World,
+        },
+    };
+
+    #[test]
+    fn resumed_snapshot_gives_same_checksum() {
+        let mut original = world(7);
+        run(&mut original, 150);
+        let json = original.snapshot().to_json();
+        let mut resumed = World::from_snapshot(Snapshot::from_json(&json).unwrap()).unwrap();
+        assert_eq!(resumed.ticks, original.ticks);
+        assert_eq!(checksum(&resumed), checksum(&original));
+        run(&mut original, 150);
+        run(&mut resumed, 150);
+        assert_eq!(checksum(&resumed), checksum(&original));
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let world = World::new(7, Config::default());
+        let mut json: serde_json::Value =
+            serde_json::from_str(&world.snapshot().to_json()).unwrap();
+        json["version"] = (SNAPSHOT_VERSION + 1).into();
+        assert!(matches!(
+            Snapshot::from_json(&json.to_string()),
+            Err(SnapshotError::UnsupportedVersion(version)) if version == SNAPSHOT_VERSION + 1
+        ));
+    }
+}
@@ -0,0 +1,299 @@
+use crate::{boundary::Boundary, creature::Creature};
+use rayon::prelude::*;
+
+/// A uniform grid over the world for finding creatures (or anything else with a bounding circle)
+/// near a point quickly.
+///
+/// Each creature is filed under every cell its bounding circle overlaps. The grid must be rebuilt
+/// whenever creatures move; queries return indices into the creatures it was built from.
+pub struct SpatialGrid {
+    world_size: glam::Vec2,
+    boundary: Boundary,
+    cell_size: glam::Vec2,
+    columns: usize,
+    rows: usize,
+    /// The creatures filed under cell `i` are `entries[cell_starts[i]..cell_starts[i + 1]]`. Keeping
+    /// every cell in one allocation makes building and querying large grids much faster.
+    cell_starts: Vec<usize>,
+    entries: Vec<usize>,
+    /// Bounding circle (center, radius) of each creature.
+    bounds: Vec<(glam::Vec2, f32)>,
+}
+
+impl SpatialGrid {
+    /// An empty grid covering `-world_size` to `world_size`, with cells about `cell_size` across.
+    pub fn new(world_size: glam::Vec2, boundary: Boundary, cell_size: f32) -> Self {
+        assert!(cell_size > 0.0);
+        let columns = ((world_size.x * 2.0 / cell_size).ceil() as usize).max(1);
+        let rows = ((world_size.y * 2.0 / cell_size).ceil() as usize).max(1);
+        Self {
+            world_size,
+            boundary,
+            // Cells exactly tile the world, so wrapping around an edge lands on a cell boundary.
+            cell_size: world_size * 2.0 / glam::Vec2::new(columns as f32, rows as f32),
+            columns,
+            rows,
+            cell_starts: vec![0; columns * rows + 1],
+            entries: Vec::new(),
+            bounds: Vec::new(),
+        }
+    }
+
+    /// A grid holding the given creatures, with cells sized to the typical creature.
+    pub fn build(world_size: glam::Vec2, boundary: Boundary, creatures: &[Creature]) -> Self {
+        let bounds = creatures
+            .par_iter()
+            .map(|creature| (creature.position, creature.radius()))
+            .collect();
+        Self::from_bounds(world_size, boundary, bounds)
+    }
+
+    /// A grid holding anything with a bounding circle, given as (center, radius). Cells are sized
+    /// to the typical circle.
+    pub fn from_bounds(
+        world_size: glam::Vec2,
+        boundary: Boundary,
+        bounds: Vec<(glam::Vec2, f32)>,
+    ) -> Self {
+        let mean_radius =
+            bounds.iter().map(|(_, radius)| radius).sum::<f32>() / bounds.len().max(1) as f32;
+        // Don't let the grid have many more cells than there are circles to put in them.
+        let area = world_size.x * world_size.y * 4.0;
+        let min_cell_size = (area / (bounds.len() * 4 + 1) as f32).sqrt();
+        let mut grid = Self::new(
+            world_size,
+            boundary,
+            (mean_radius * 2.0).max(min_cell_size).max(1.0),
+        );
+        // Count the entries in each cell, then fill them in.
+        let cell_count = grid.columns * grid.rows;
+        let mut cell_starts = vec![0; cell_count + 1];
+        for &(center, radius) in bounds.iter() {
+            grid.for_each_cell_overlapping(center - radius, center + radius, |cell| {
+                cell_starts[cell + 1] += 1;
+            });
+        }
+        for cell in 0..cell_count {
+            cell_starts[cell + 1] += cell_starts[cell];
+        }
+        let mut next_entry = cell_starts.clone();
+        let mut entries = vec![0; cell_starts[cell_count]];
+        for (index, &(center, radius)) in bounds.iter().enumerate() {
+            grid.for_each_cell_overlapping(center - radius, center + radius, |cell| {
+                entries[next_entry[cell]] = index;
+                next_entry[cell] += 1;
+            });
+        }
+        grid.cell_starts = cell_starts;
+        grid.entries = entries;
+        grid.bounds = bounds;
+        grid
+    }
+
+    /// Calls `f` with the index of every cell overlapping the box. Without wrapping, parts of the
+    /// box outside the grid are clamped to the nearest cells.
+    fn for_each_cell_overlapping(
+        &self,
+        min: glam::Vec2,
+        max: glam::Vec2,
+        mut f: impl FnMut(usize),
+    ) {
+        let min_cell = ((min + self.world_size) / self.cell_size).floor();
+        let max_cell = ((max + self.world_size) / self.cell_size).floor();
+        // Indices along an axis are wrapped by `rem_euclid`, which leaves clamped indices as they
+        // are.
+        let axis = |min: f32, max: f32, count: usize| -> std::ops::RangeInclusive<i64> {
+            let count = count as i64;
+            match self.boundary {
+                Boundary::Walls => {
+                    (min as i64).clamp(0, count - 1)..=(max as i64).clamp(0, count - 1)
+                }
+                Boundary::Toroidal => {
+                    let min = min as i64;
+                    // A box wider than the world covers every cell exactly once.
+                    min..=(max as i64).min(min + count - 1)
+                }
+            }
+        };
+        let columns = axis(min_cell.x, max_cell.x, self.columns);
+        let rows = axis(min_cell.y, max_cell.y, self.rows);
+        for row in rows {
+            let row = row.rem_euclid(self.rows as i64) as usize;
+            for column in columns.clone() {
+                let column = column.rem_euclid(self.columns as i64) as usize;
+                f(row * self.columns + column);
+            }
+        }
+    }
+
+    /// Indices of creatures filed in cells overlapping the box, sorted and without duplicates.
+    fn candidates(&self, min: glam::Vec2, max: glam::Vec2) -> Vec<usize> {
+        let mut candidates = Vec::new();
+        self.for_each_cell_overlapping(min, max, |cell| {
+            candidates.extend(&self.entries[self.cell_starts[cell]..self.cell_starts[cell + 1]]);
+        });
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates
+    }
+
+    /// Indices of creatures whose bounding circle overlaps the given circle, in ascending order.
+    pub fn query_circle(&self, center: glam::Vec2, radius: f32) -> Vec<usize> {
+        let mut candidates = self.candidates(center - radius, center + radius);
+        candidates.retain(|&index| {
+            let (other_center, other_radius) = self.bounds[index];
+            let max_distance = radius + other_radius;
+            self.boundary
+                .distance_squared(center, other_center, self.world_size)
+                <= max_distance * max_distance
+        });
+        candidates
+    }
+
+    /// Indices of creatures whose bounding circle overlaps the given box, in ascending order.
+    pub fn query_aabb(&self, min: glam::Vec2, max: glam::Vec2) -> Vec<usize> {
+        let mut candidates = self.candidates(min, max);
+        let box_center = (min + max) / 2.0;
+        let half_extent = (max - min) / 2.0;
+        candidates.retain(|&index| {
+            let (center, radius) = self.bounds[index];
+            // Work relative to the box center so the closest copy of the circle is used when
+            // wrapping.
+            let offset = self.boundary.offset(box_center, center, self.world_size);
+            offset
+                .clamp(-half_extent, half_extent)
+                .distance_squared(offset)
+                <= radius * radius
+        });
+        candidates
+    }
+
+    pub fn world_size(&self) -> glam::Vec2 {
+        self.world_size
+    }
+
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    /// The bounding circle of a creature, as (center, radius).
+    pub fn bounds(&self, index: usize) -> (glam::Vec2, f32) {
+        self.bounds[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creature::SimRng;
+    use rand::Rng;
+
+    const WORLD_SIZE: glam::Vec2 = glam::Vec2::new(100.0, 60.0);
+
+    fn random_point(rng: &mut SimRng) -> glam::Vec2 {
+        glam::Vec2::new(
+            rng.gen_range(-WORLD_SIZE.x..WORLD_SIZE.x),
+            rng.gen_range(-WORLD_SIZE.y..WORLD_SIZE.y),
+        )
+    }
+
+    fn random_bounds(rng: &mut SimRng) -> Vec<(glam::Vec2, f32)> {
+        (0..200)
+            .map(|_| (random_point(rng), rng.gen_range(0.5..20.0)))
+            .collect()
+    }
+
+    /// Where copies of something appear when the world wraps: every whole world away, counting
+    /// the original.
+    fn shifts(boundary: Boundary) -> Vec<glam::Vec2> {
+        match boundary {
+            Boundary::Walls => vec![glam::Vec2::ZERO],
+            Boundary::Toroidal => {
+                let extent = WORLD_SIZE * 2.0;
+                let mut shifts = Vec::new();
+                for y in -1..=1 {
+                    for x in -1..=1 {
+                        shifts.push(glam::Vec2::new(x as f32, y as f32) * extent);
+                    }
+                }
+                shifts
+            }
+        }
+    }
+
+    fn brute_force_circle(
+        bounds: &[(glam::Vec2, f32)],
+        boundary: Boundary,
+        center: glam::Vec2,
+        radius: f32,
+    ) -> Vec<usize> {
+        (0..bounds.len())
+            .filter(|&index| {
+                let (other_center, other_radius) = bounds[index];
+                shifts(boundary)
+                    .iter()
+                    .any(|&shift| (other_center + shift).distance(center) <= radius + other_radius)
+            })
+            .collect()
+    }
+
+    fn brute_force_aabb(
+        bounds: &[(glam::Vec2, f32)],
+        boundary: Boundary,
+        min: glam::Vec2,
+        max: glam::Vec2,
+    ) -> Vec<usize> {
+        (0..bounds.len())
+            .filter(|&index| {
+                let (center, radius) = bounds[index];
+                shifts(boundary).iter().any(|&shift| {
+                    let center = center + shift;
+                    center.clamp(min, max).distance(center) <= radius
+                })
+            })
+            .collect()
+    }
+
+    fn queries_match_brute_force(boundary: Boundary) {
+        let mut rng = SimRng::new(3, 0);
+        for _ in 0..10 {
+            let bounds = random_bounds(&mut rng);
+            let grid = SpatialGrid::from_bounds(WORLD_SIZE, boundary, bounds.clone());
+            for _ in 0..100 {
+                // Queries near the edges stick out of the world.
+                let center = random_point(&mut rng);
+                let radius = rng.gen_range(0.0..40.0);
+                assert_eq!(
+                    grid.query_circle(center, radius),
+                    brute_force_circle(&bounds, boundary, center, radius)
+                );
+                let half_extent =
+                    glam::Vec2::new(rng.gen_range(0.0..40.0), rng.gen_range(0.0..40.0));
+                let (min, max) = (center - half_extent, center + half_extent);
+                assert_eq!(
+                    grid.query_aabb(min, max),
+                    brute_force_aabb(&bounds, boundary, min, max)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn queries_match_brute_force_with_walls() {
+        queries_match_brute_force(Boundary::Walls);
+    }
+
+    #[test]
+    fn queries_match_brute_force_when_wrapping() {
+        queries_match_brute_force(Boundary::Toroidal);
+    }
+
+    #[test]
+    fn empty_grid_finds_nothing() {
+        let grid = SpatialGrid::from_bounds(WORLD_SIZE, Boundary::Toroidal, Vec::new());
+        assert!(grid.query_circle(glam::Vec2::ZERO, 1000.0).is_empty());
+        assert!(grid
+            .query_aabb(-WORLD_SIZE * 2.0, WORLD_SIZE * 2.0)
+            .is_empty());
+    }
+}
@@ -0,0 +1,372 @@
+//! Groups the population into species of similar body plans.
+
+use crate::{
+    config::SpeciesConfig,
+    creature::{indices_by_id, Creature, Segment, SegmentType},
+    genome::Genome,
+    periodic::{Periodic, Schedule},
+    world::World,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, io::Write, path::Path};
+
+/// How different two body plans are. Zero for identical bodies, and symmetric.
+///
+/// Adds the difference in how much length goes to each segment type, as a fraction of the total
+/// length, to how far each segment is from the most similar segment of the other body.
+pub fn body_plan_distance(a: &[Segment], b: &[Segment], config: &SpeciesConfig) -> f32 {
+    composition_distance(a, b) + geometry_distance(a, b, config) / config.geometry_scale
+}
+
+fn type_lengths(segments: &[Segment]) -> [f32; SegmentType::ALL.len()] {
+    let mut lengths = [0.0; SegmentType::ALL.len()];
+    for segment in segments.iter() {
+        let i = SegmentType::ALL
+            .iter()
+            .position(|&t| t == segment.t)
+            .unwrap();
+        lengths[i] += segment.length();
+    }
+    lengths
+}
+
+fn composition_distance(a: &[Segment], b: &[Segment]) -> f32 {
+    let a_lengths = type_lengths(a);
+    let b_lengths = type_lengths(b);
+    let total: f32 = a_lengths.iter().sum::<f32>() + b_lengths.iter().sum::<f32>();
+    if total == 0.0 {
+        return 0.0;
+    }
+    let difference: f32 = a_lengths
+        .iter()
+        .zip(b_lengths.iter())
+        .map(|(a, b)| (a - b).abs())
+        .sum();
+    difference / total
+}
+
+fn segment_distance(a: &Segment, b: &Segment, config: &SpeciesConfig) -> f32 {
+    let penalty = if a.t == b.t {
+        0.0
+    } else {
+        config.type_mismatch_penalty
+    };
+    a.midpoint().distance(b.midpoint()) + (a.length() - b.length()).abs() + penalty
+}
+
+/// Mean distance from each segment to its nearest counterpart, averaged over both directions.
+fn geometry_distance(a: &[Segment], b: &[Segment], config: &SpeciesConfig) -> f32 {
+    let one_way = |from: &[Segment], to: &[Segment]| -> f32 {
+        let total: f32 = from
+            .iter()
+            .map(|f| {
+                to.iter()
+                    .map(|t| segment_distance(f, t, config))
+                    .fold(f32::INFINITY, f32::min)
+            })
+            .sum();
+        total / from.len() as f32
+    };
+    match (a.is_empty(), b.is_empty()) {
+        (true, true) => 0.0,
+        (true, false) | (false, true) => f32::INFINITY,
+        (false, false) => (one_way(a, b) + one_way(b, a)) / 2.0,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Species {
+    /// Stays the same for as long as the species has members.
+    pub id: u64,
+    /// Simulated time the species was first seen.
+    pub founded: f64,
+    /// The member whose body plan stands for the species.
+    pub representative_id: u64,
+    pub representative: Genome,
+    /// IDs of the creatures in the species at the last clustering pass.
+    pub members: Vec<u64>,
+}
+
+impl Species {
+    pub fn count(&self) -> usize {
+        self.members.len()
+    }
+}
+
+/// How big a species was at one clustering pass.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpeciesSample {
+    pub time: f64,
+    pub species_id: u64,
+    pub count: usize,
+    pub representative_id: u64,
+}
+
+const CSV_HEADER: &str = "time,species,count,representative";
+
+/// Clusters a World into species every `species.interval` seconds of simulated time.
+///
+/// Each creature joins the existing species with the nearest representative, if it is within
+/// `species.threshold`, and founds a new species otherwise. Species keep their IDs between passes
+/// and their representatives follow the members, so a slowly drifting species is not renamed.
+///
+/// The tracker is saved in snapshots so species keep their IDs when a run is resumed. The samples
+/// taken so far are not.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeciesTracker {
+    schedule: Schedule,
+    next_id: u64,
+    species: Vec<Species>,
+    species_of: BTreeMap<u64, u64>,
+    #[serde(skip)]
+    samples: Vec<SpeciesSample>,
+}
+
+impl SpeciesTracker {
+    pub fn new(world: &World) -> Self {
+        Self {
+            schedule: Schedule::new(world.config.species.interval as f64, world.time()),
+            next_id: 1,
+            species: Vec::new(),
+            species_of: BTreeMap::new(),
+            samples: Vec::new(),
+        }
+    }
+
+    /// Reassigns every creature to a species, now.
+    pub fn cluster(&mut self, world: &World) {
+        let config = &world.config.species;
+        for species in self.species.iter_mut() {
+            species.members.clear();
+        }
+        self.species_of.clear();
+        // Creatures are assigned in ID order (see `indices_by_id`).
+        for index in indices_by_id(&world.creatures) {
+            let creature = &world.creatures[index];
+            let nearest = self
+                .species
+                .iter()
+                .enumerate()
+                .map(|(i, species)| {
+                    let distance = body_plan_distance(
+                        &creature.segments,
+                        &species.representative.segments,
+                        config,
+                    );
+                    (i, distance)
+                })
+                .filter(|&(_, distance)| distance < config.threshold)
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            let i = match nearest {
+                Some((i, _)) => i,
+                None => {
+                    self.species.push(Species {
+                        id: self.next_id,
+                        founded: world.time(),
+                        representative_id: creature.id,
+                        representative: creature.genome(),
+                        members: Vec::new(),
+                    });
+                    self.next_id += 1;
+                    self.species.len() - 1
+                }
+            };
+            self.species[i].members.push(creature.id);
+            self.species_of.insert(creature.id, self.species[i].id);
+        }
+        self.species.retain(|species| !species.members.is_empty());
+        // Hand the representative on to the member nearest the old one, so it tracks the species
+        // as it evolves.
+        let creatures: BTreeMap<u64, &Creature> =
+            world.creatures.iter().map(|c| (c.id, c)).collect();
+        for species in self.species.iter_mut() {
+            let nearest = species
+                .members
+                .iter()
+                .map(|id| creatures[id])
+                .min_by(|a, b| {
+                    let a =
+                        body_plan_distance(&a.segments, &species.representative.segments, config);
+                    let b =
+                        body_plan_distance(&b.segments, &species.representative.segments, config);
+                    a.total_cmp(&b)
+                })
+                .unwrap();
+            species.representative_id = nearest.id;
+            species.representative = nearest.genome();
+        }
+    }
+
+    /// Living species, oldest first.
+    pub fn species(&self) -> &[Species] {
+        &self.species
+    }
+
+    /// The species a creature was put in at the last clustering pass.
+    pub fn species_of(&self, creature_id: u64) -> Option<u64> {
+        self.species_of.get(&creature_id).copied()
+    }
+
+    /// Member counts by species ID.
+    pub fn counts(&self) -> BTreeMap<u64, usize> {
+        self.species
+            .iter()
+            .map(|species| (species.id, species.count()))
+            .collect()
+    }
+
+    /// Every living species at every pass so far, oldest first.
+    pub fn samples(&self) -> &[SpeciesSample] {
+        &self.samples
+    }
+
+    pub fn write_csv(&self, mut writer: impl Write) -> std::io::Result<()> {
+        writeln!(writer, "{}", CSV_HEADER)?;
+        for sample in self.samples.iter() {
+            writeln!(
+                writer,
+                "{},{},{},{}",
+                sample.time, sample.species_id, sample.count, sample.representative_id,
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn save_csv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_csv(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Writes the living species, with their representatives' genomes, as a JSON array.
+    pub fn save_species(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(&self.species)?)
+    }
+}
+
+impl Periodic for SpeciesTracker {
+    fn schedule(&mut self) -> &mut Schedule {
+        &mut self.schedule
+    }
+
+    /// Clusters the population and samples the species sizes.
+    fn pass(&mut self, world: &World) {
+        self.cluster(world);
+        let time = world.time();
+        self.samples
+            .extend(self.species.iter().map(|species| SpeciesSample {
+                time,
+                species_id: species.id,
+                count: species.count(),
+                representative_id: species.representative_id,
+            }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, creature::tests::segment};
+
+    fn nudged() -> Genome {
+        let mut genome = Genome::default();
+        genome.segments[2].b += glam::Vec2::new(2.0, 0.0);
+        genome
+    }
+
+    fn line() -> Genome {
+        Genome {
+            segments: vec![segment((0.0, 0.0), (0.0, 60.0), SegmentType::Defend)],
+        }
+    }
+
+    fn world(genomes: Vec<Genome>) -> World {
+        let mut world = World::new(7, Config::default());
+        for genome in genomes {
+            world.spawn(genome, glam::Vec2::ZERO).unwrap();
+        }
+        world
+    }
+
+    fn species_ids(tracker: &SpeciesTracker) -> Vec<(u64, Vec<u64>)> {
+        tracker
+            .species()
+            .iter()
+            .map(|species| (species.id, species.members.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn distance_is_zero_for_identical_bodies_and_symmetric() {
+        let config = SpeciesConfig::default();
+        let a = Genome::default().segments;
+        assert_eq!(body_plan_distance(&a, &a, &config), 0.0);
+        for b in [nudged().segments, line().segments] {
+            let distance = body_plan_distance(&a, &b, &config);
+            assert!(distance > 0.0);
+            assert_eq!(distance, body_plan_distance(&b, &a, &config));
+        }
+        assert!(body_plan_distance(&a, &nudged().segments, &config) < config.threshold);
+        assert!(body_plan_distance(&a, &line().segments, &config) > config.threshold);
+    }
+
+    #[test]
+    fn species_keep_their_ids_between_passes() {
+        let mut world = world(vec![Genome::default(), line(), nudged()]);
+        let mut tracker = SpeciesTracker::new(&world);
+        tracker.cluster(&world);
+        assert_eq!(species_ids(&tracker), vec![(1, vec![1, 3]), (2, vec![2])]);
+        world.creatures.reverse();
+        world.spawn(line(), glam::Vec2::ZERO).unwrap();
+        tracker.cluster(&world);
+        assert_eq!(
+            species_ids(&tracker),
+            vec![(1, vec![1, 3]), (2, vec![2, 4])]
+        );
+        assert_eq!(tracker.species_of(4), Some(2));
+    }
+
+    #[test]
+    fn clustering_does_not_depend_on_creature_order() {
+        let genomes = vec![nudged(), line(), Genome::default(), line()];
+        let forward = world(genomes.clone());
+        let mut reversed = world(genomes);
+        reversed.creatures.reverse();
+        let mut a = SpeciesTracker::new(&forward);
+        let mut b = SpeciesTracker::new(&reversed);
+        a.cluster(&forward);
+        b.cluster(&reversed);
+        assert_eq!(a.species(), b.species());
+        assert_eq!(species_ids(&a), vec![(1, vec![1, 3]), (2, vec![2, 4])]);
+    }
+
+    #[test]
+    fn representatives_move_to_members() {
+        let mut world = world(vec![Genome::default(), nudged()]);
+        let mut tracker = SpeciesTracker::new(&world);
+        tracker.cluster(&world);
+        assert_eq!(tracker.species()[0].representative_id, 1);
+        world.creatures.retain(|creature| creature.id != 1);
+        tracker.cluster(&world);
+        let species = &tracker.species()[0];
+        assert_eq!(species.id, 1);
+        assert_eq!(species.representative_id, 2);
+        assert_eq!(species.representative, nudged());
+    }
+
+    #[test]
+    fn species_without_members_are_dropped() {
+        let mut world = world(vec![Genome::default(), line()]);
+        let mut tracker = SpeciesTracker::new(&world);
+        tracker.cluster(&world);
+        assert_eq!(tracker.species().len(), 2);
+        world.creatures.retain(|creature| creature.id != 1);
+        tracker.cluster(&world);
+        assert_eq!(species_ids(&tracker), vec![(2, vec![2])]);
+        assert_eq!(tracker.species_of(1), None);
+        // A new creature like the dead one founds a new species rather than reviving the old one.
+        world.spawn(Genome::default(), glam::Vec2::ZERO).unwrap();
+        tracker.cluster(&world);
+        assert_eq!(species_ids(&tracker), vec![(2, vec![2]), (3, vec![3])]);
+    }
+}
@@ -0,0 +1,253 @@
+//! Population statistics sampled over a run, for plotting in external tools.
+
+use crate::{
+    creature::{DeathCause, SegmentLengths},
+    periodic::{Periodic, Schedule},
+    world::World,
+};
+use serde::{Deserialize, Serialize};
+use std::{io::Write, path::Path};
+
+/// How many creatures have died of each cause.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeathCounts {
+    pub starvation: u64,
+    pub wall: u64,
+    pub attack: u64,
+    pub intervention: u64,
+}
+
+impl DeathCounts {
+    pub fn record(&mut self, cause: DeathCause) {
+        match cause {
+            DeathCause::Starvation => self.starvation += 1,
+            DeathCause::Wall => self.wall += 1,
+            DeathCause::Attack => self.attack += 1,
+            DeathCause::Intervention => self.intervention += 1,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.starvation + self.wall + self.attack + self.intervention
+    }
+
+    fn since(&self, earlier: &DeathCounts) -> DeathCounts {
+        DeathCounts {
+            starvation: self.starvation - earlier.starvation,
+            wall: self.wall - earlier.wall,
+            attack: self.attack - earlier.attack,
+            intervention: self.intervention - earlier.intervention,
+        }
+    }
+}
+
+/// Aggregates over the population at one moment. Births and deaths are counted since the previous
+/// sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatsSample {
+    pub time: f64,
+    pub population: usize,
+    pub corpses: usize,
+    pub births: u64,
+    pub deaths: DeathCounts,
+    pub mean_energy: f32,
+    pub mean_segment_lengths: SegmentLengths,
+    pub mean_radius: f32,
+}
+
+impl StatsSample {
+    pub fn new(world: &World, births: u64, deaths: DeathCounts) -> Self {
+        let mut mean_energy = 0.0;
+        let mut mean_radius = 0.0;
+        let mut mean_segment_lengths = SegmentLengths {
+            energy: 0.0,
+            attack: 0.0,
+            defend: 0.0,
+            move_: 0.0,
+            total: 0.0,
+        };
+        for creature in world.creatures.iter() {
+            mean_energy += creature.energy;
+            mean_radius += creature.radius();
+            let segment_lengths = creature.segment_lengths();
+            mean_segment_lengths.energy += segment_lengths.energy;
+            mean_segment_lengths.attack += segment_lengths.attack;
+            mean_segment_lengths.defend += segment_lengths.defend;
+            mean_segment_lengths.move_ += segment_lengths.move_;
+            mean_segment_lengths.total += segment_lengths.total;
+        }
+        let population = world.creatures.len();
+        let n = population.max(1) as f32;
+        mean_segment_lengths.energy /= n;
+        mean_segment_lengths.attack /= n;
+        mean_segment_lengths.defend /= n;
+        mean_segment_lengths.move_ /= n;
+        mean_segment_lengths.total /= n;
+        Self {
+            time: world.time(),
+            population,
+            corpses: world.corpses.len(),
+            births,
+            deaths,
+            mean_energy: mean_energy / n,
+            mean_segment_lengths,
+            mean_radius: mean_radius / n,
+        }
+    }
+}
+
+const CSV_HEADER: &str = "time,population,corpses,births,deaths_starvation,deaths_wall,\
+deaths_attack,deaths_intervention,mean_energy,mean_energy_length,mean_attack_length,\
+mean_defend_length,mean_move_length,mean_total_length,mean_radius";
+
+/// Samples a World every `stats.interval` seconds of simulated time.
+pub struct StatsCollector {
+    schedule: Schedule,
+    last_births: u64,
+    last_deaths: DeathCounts,
+    samples: Vec<StatsSample>,
+}
+
+impl StatsCollector {
+    /// Starts counting births and deaths from the world's current totals.
+    pub fn new(world: &World) -> Self {
+        Self {
+            schedule: Schedule::new(world.config.stats.interval as f64, world.time()),
+            last_births: world.births,
+            last_deaths: world.deaths,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn samples(&self) -> &[StatsSample] {
+        &self.samples
+    }
+
+    pub fn write_csv(&self, mut writer: impl Write) -> std::io::Result<()> {
+        writeln!(writer, "{}", CSV_HEADER)?;
+        for sample in self.samples.iter() {
+            let lengths = &sample.mean_segment_lengths;
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                sample.time,
+                sample.population,
+                sample.corpses,
+                sample.births,
+                sample.deaths.starvation,
+                sample.deaths.wall,
+                sample.deaths.attack,
+                sample.deaths.intervention,
+                sample.mean_energy,
+                lengths.energy,
+                lengths.attack,
+                lengths.defend,
+                lengths.move_,
+                lengths.total,
+                sample.mean_radius,
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn save_csv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_csv(&mut writer)?;
+        writer.flush()
+    }
+}
+
+impl Periodic for StatsCollector {
+    fn schedule(&mut self) -> &mut Schedule {
+        &mut self.schedule
+    }
+
+    /// Takes a sample.
+    fn pass(&mut self, world: &World) {
+        self.samples.push(StatsSample::new(
+            world,
+            world.births - self.last_births,
+            world.deaths.since(&self.last_deaths),
+        ));
+        self.last_births = world.births;
+        self.last_deaths = world.deaths;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, genome::Genome};
+
+    #[test]
+    fn deaths_since_counts_each_cause() {
+        let mut earlier = DeathCounts::default();
+        earlier.record(DeathCause::Starvation);
+        earlier.record(DeathCause::Attack);
+        let mut later = earlier;
+        later.record(DeathCause::Attack);
+        later.record(DeathCause::Wall);
+        later.record(DeathCause::Attack);
+        assert_eq!(
+            later.since(&earlier),
+            DeathCounts {
+                starvation: 0,
+                wall: 1,
+                attack: 2,
+                intervention: 0,
+            }
+        );
+        assert_eq!(later.since(&earlier).total(), 3);
+    }
+
+    #[test]
+    fn samples_are_taken_every_interval() {
+        let mut world = World::new(7, Config::default());
+        world.spawn(Genome::default(), glam::Vec2::ZERO).unwrap();
+        let mut collector = StatsCollector::new(&world);
+        for _ in 0..250 {
+            world.update();
+            collector.observe(&world);
+        }
+        let times: Vec<f64> = collector.samples().iter().map(|s| s.time).collect();
+        assert_eq!(times, vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn csv_rows_follow_the_header() {
+        let mut world = World::new(7, Config::default());
+        world.spawn(Genome::default(), glam::Vec2::ZERO).unwrap();
+        let mut collector = StatsCollector::new(&world);
+        world.creatures[0].kill(DeathCause::Intervention);
+        world.spawn(Genome::default(), glam::Vec2::ZERO).unwrap();
+        for _ in 0..120 {
+            world.update();
+            collector.observe(&world);
+        }
+        let mut csv = Vec::new();
+        collector.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], CSV_HEADER);
+        let row: Vec<&str> = lines[1].split(',').collect();
+        assert_eq!(row.len(), CSV_HEADER.split(',').count());
+        assert_eq!(row[..8], ["1", "1", "1", "0", "0", "0", "0", "1"]);
+        let creature = &world.creatures[0];
+        let lengths = creature.segment_lengths();
+        let expected = [
+            creature.energy,
+            lengths.energy,
+            lengths.attack,
+            lengths.defend,
+            lengths.move_,
+            lengths.total,
+            creature.radius(),
+        ];
+        let means: Vec<f32> = row[8..]
+            .iter()
+            .map(|value| value.parse().unwrap())
+            .collect();
+        assert_eq!(means, expected);
+    }
+}
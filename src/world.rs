@@ -0,0 +1,459 @@
+use crate::{
+    body_plan::{validate_body_plan, BodyPlanError},
+    collision::{find_contacts, Contact},
+    combat::resolve_combat,
+    config::Config,
+    corpse::{scavenge, Corpse},
+    creature::{indices_by_id, random_normal_vec2, Creature, DeathCause, SimRng},
+    genome::Genome,
+    lineage::Lineage,
+    snapshot::{Snapshot, SnapshotError, SNAPSHOT_VERSION},
+    spatial::SpatialGrid,
+    stats::DeathCounts,
+};
+use rand::Rng as _;
+use rayon::prelude::*;
+
+/// The random stream used for world level decisions. Creature streams start after this one.
+const WORLD_STREAM: u64 = 0;
+
+/// A population of creatures that are updated together.
+///
+/// The world is pure simulation state; it knows nothing about windows or GPUs and can be stepped
+/// headless.
+///
+/// All randomness is drawn from streams derived from `seed`, so the same seed, initial body plans
+/// and sequence of `update` calls always produce bit-identical trajectories. This holds however
+/// many threads the update runs on.
+pub struct World {
+    pub creatures: Vec<Creature>,
+    /// Segments of different creatures that were touching at the end of the last update.
+    pub contacts: Vec<Contact>,
+    /// Dead creatures that have not yet been eaten or decayed away.
+    pub corpses: Vec<Corpse>,
+    /// Broadphase over the creatures, rebuilt every update.
+    grid: SpatialGrid,
+    pub seed: u64,
+    rng: SimRng,
+    next_id: u64,
+    /// Updates since the world was created.
+    pub ticks: u64,
+    /// Creatures born by reproduction since the world was created.
+    pub births: u64,
+    /// Creatures that have died since the world was created.
+    pub deaths: DeathCounts,
+    pub lineage: Lineage,
+    pub config: Config,
+}
+
+impl World {
+    /// An empty world. Its size, boundary and resource field are in `config.world`.
+    pub fn new(seed: u64, config: Config) -> Self {
+        let (size, boundary) = (config.world.size, config.world.boundary);
+        Self {
+            creatures: Vec::new(),
+            contacts: Vec::new(),
+            corpses: Vec::new(),
+            grid: SpatialGrid::new(size, boundary, size.max_element().max(1.0)),
+            seed,
+            rng: SimRng::new(seed, WORLD_STREAM),
+            next_id: WORLD_STREAM + 1,
+            ticks: 0,
+            births: 0,
+            deaths: DeathCounts::default(),
+            lineage: Lineage::default(),
+            config,
+        }
+    }
+
+    /// A World resumed from a snapshot. Updating it gives exactly the same results as updating the
+    /// World the snapshot was taken from.
+    ///
+    /// Fails if the snapshot's config is out of range or a creature's body breaks its limits.
+    pub fn from_snapshot(snapshot: Snapshot) -> Result<Self, SnapshotError> {
+        snapshot.config.validate()?;
+        for creature in snapshot.creatures.iter() {
+            validate_body_plan(&creature.segments, &snapshot.config.body).map_err(|error| {
+                SnapshotError::InvalidCreature {
+                    id: creature.id,
+                    error,
+                }
+            })?;
+        }
+        let mut world = Self::new(snapshot.seed, snapshot.config);
+        world.ticks = snapshot.ticks;
+        world.births = snapshot.births;
+        world.deaths = snapshot.deaths;
+        world.lineage = snapshot.lineage;
+        world.next_id = snapshot.next_id;
+        world.rng = snapshot.rng;
+        world.creatures = snapshot.creatures;
+        world.corpses = snapshot.corpses;
+        world.grid = SpatialGrid::build(
+            world.config.world.size,
+            world.config.world.boundary,
+            &world.creatures,
+        );
+        world.contacts = find_contacts(&world.creatures, &world.grid);
+        Ok(world)
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            seed: self.seed,
+            ticks: self.ticks,
+            births: self.births,
+            deaths: self.deaths,
+            lineage: self.lineage.clone(),
+            config: self.config.clone(),
+            next_id: self.next_id,
+            rng: self.rng.clone(),
+            creatures: self.creatures.clone(),
+            corpses: self.corpses.clone(),
+            species: None,
+        }
+    }
+
+    /// Spawns one creature for each genome, scattered randomly within the middle of the world.
+    /// Fails on the first genome whose body breaks the limits in `config.body`.
+    pub fn from_genomes(
+        genomes: impl IntoIterator<Item = Genome>,
+        seed: u64,
+        config: Config,
+    ) -> Result<Self, BodyPlanError> {
+        let mut world = Self::new(seed, config);
+        for genome in genomes {
+            let position = world.random_spawn_position();
+            world.spawn(genome, position)?;
+        }
+        Ok(world)
+    }
+
+    /// Spawns `population` creatures with random body plans, scattered randomly within the middle
+    /// of the world. The body plans are drawn from the world's seed too.
+    pub fn from_random_genomes(
+        population: usize,
+        seed: u64,
+        config: Config,
+    ) -> Result<Self, BodyPlanError> {
+        let mut world = Self::new(seed, config);
+        for _ in 0..population {
+            let genome = Genome::random(&world.config, &mut world.rng);
+            let position = world.random_spawn_position();
+            world.spawn(genome, position)?;
+        }
+        Ok(world)
+    }
+
+    fn random_spawn_position(&mut self) -> glam::Vec2 {
+        let spawn_size = self.config.world.size / 2.0;
+        glam::Vec2::new(
+            self.rng.gen_range(-spawn_size.x..=spawn_size.x),
+            self.rng.gen_range(-spawn_size.y..=spawn_size.y),
+        )
+    }
+
+    /// Adds a creature with the given body plan and returns its ID. Positions that would leave part
+    /// of the body outside the world are moved inside (see `Creature::place_inside`). Fails,
+    /// leaving the world unchanged, if the body breaks the limits in `config.body`.
+    pub fn spawn(&mut self, genome: Genome, position: glam::Vec2) -> Result<u64, BodyPlanError> {
+        genome.validate(&self.config.body)?;
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut creature = Creature::new(id, SimRng::new(self.seed, id), genome, position);
+        creature.place_inside(self.config.world.size, self.config.world.boundary);
+        creature.momentum =
+            random_normal_vec2(&mut creature.rng) * self.config.creature.spawn_speed;
+        creature.energy = creature.energy_capacity(&self.config.creature) / 2.0;
+        self.lineage.record_birth(id, None, self.time());
+        self.creatures.push(creature);
+        Ok(id)
+    }
+
+    /// The ID the next creature to be born or spawned will get.
+    pub fn next_id(&self) -> u64 {
+        self.next_id
+    }
+
+    /// The random stream for world level decisions.
+    pub fn rng(&self) -> &SimRng {
+        &self.rng
+    }
+
+    /// Simulated seconds since the world was created. Counted in whole ticks, so it doesn't drift
+    /// however long the world runs.
+    pub fn time(&self) -> f64 {
+        // Dividing by the tick rate rather than multiplying by `delta_time` gives round numbers,
+        // like 100 rather than 100.00000000000001, whenever the time is a whole number of seconds.
+        self.ticks as f64 / (1.0 / self.config.world.delta_time)
+    }
+
+    /// Advances the world by one tick of `config.world.delta_time` seconds.
+    pub fn update(&mut self) {
+        let delta_time = self.config.world.delta_time as f32;
+        let config = &self.config;
+        let (size, boundary) = (config.world.size, config.world.boundary);
+        let resource_field = &config.world.resource_field;
+        // Creatures only touch their own state here, so they can be updated in parallel.
+        self.creatures.par_iter_mut().for_each(|creature| {
+            creature.update(delta_time, size, boundary, resource_field, &config.creature);
+        });
+        // Child IDs are handed out in creature ID order (see `indices_by_id`).
+        let mut offspring = Vec::new();
+        for index in indices_by_id(&self.creatures) {
+            let creature = &mut self.creatures[index];
+            if !creature.can_reproduce(&self.config.creature) {
+                continue;
+            }
+            let child_id = self.next_id;
+            let child = creature.reproduce(
+                child_id,
+                SimRng::new(self.seed, child_id),
+                self.config.world.size,
+                self.config.world.boundary,
+                &self.config,
+            );
+            if let Some(child) = child {
+                self.next_id += 1;
+                offspring.push(child);
+            }
+        }
+        self.births += offspring.len() as u64;
+        for child in offspring.iter() {
+            self.lineage
+                .record_birth(child.id, child.parent_id, self.time());
+        }
+        self.creatures.extend(offspring);
+        self.grid = SpatialGrid::build(
+            self.config.world.size,
+            self.config.world.boundary,
+            &self.creatures,
+        );
+        self.contacts = find_contacts(&self.creatures, &self.grid);
+        resolve_combat(
+            &mut self.creatures,
+            &self.contacts,
+            &self.config,
+            delta_time,
+        );
+        scavenge(
+            &mut self.creatures,
+            &mut self.corpses,
+            self.config.world.size,
+            self.config.world.boundary,
+            &self.config,
+            delta_time,
+        );
+        let corpse_config = &self.config.corpse;
+        self.corpses
+            .par_iter_mut()
+            .for_each(|corpse| corpse.decay(delta_time, corpse_config));
+        self.corpses
+            .retain(|corpse| !corpse.is_gone(&self.config.corpse));
+        self.remove_dead();
+        self.grid = SpatialGrid::build(
+            self.config.world.size,
+            self.config.world.boundary,
+            &self.creatures,
+        );
+        self.ticks += 1;
+    }
+
+    /// Indices of creatures whose bounding circle overlaps the given circle, as of the last update.
+    pub fn creatures_in_circle(&self, center: glam::Vec2, radius: f32) -> Vec<usize> {
+        self.grid.query_circle(center, radius)
+    }
+
+    /// Indices of creatures whose bounding circle overlaps the given box, as of the last update.
+    pub fn creatures_in_aabb(&self, min: glam::Vec2, max: glam::Vec2) -> Vec<usize> {
+        self.grid.query_aabb(min, max)
+    }
+
+    /// Turns dead creatures into corpses, and removes the contacts they were part of. Indices in
+    /// the remaining contacts are updated to match.
+    fn remove_dead(&mut self) {
+        let mut new_indices = Vec::with_capacity(self.creatures.len());
+        let mut next_index = 0;
+        for creature in self.creatures.iter() {
+            if creature.dead {
+                // Creatures marked dead without going through `kill` are counted as starved.
+                self.deaths
+                    .record(creature.death_cause.unwrap_or(DeathCause::Starvation));
+                self.lineage.record_death(creature.id, self.time());
+                new_indices.push(None);
+            } else {
+                new_indices.push(Some(next_index));
+                next_index += 1;
+            }
+        }
+        self.contacts.retain_mut(|contact| {
+            match (
+                new_indices[contact.creature_a],
+                new_indices[contact.creature_b],
+            ) {
+                (Some(a), Some(b)) => {
+                    contact.creature_a = a;
+                    contact.creature_b = b;
+                    true
+                }
+                _ => false,
+            }
+        });
+        // Corpses are added in creature ID order, since creatures eat from them in corpse order.
+        self.corpses.extend(
+            indices_by_id(&self.creatures)
+                .into_iter()
+                .map(|index| &self.creatures[index])
+                .filter(|creature| creature.dead)
+                .map(|creature| Corpse::new(creature, &self.config.corpse)),
+        );
+        self.creatures.retain(|creature| !creature.dead);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{environment::ResourceField, replay::checksum};
+
+    /// Twenty creatures with random body plans, in the default config.
+    pub(crate) fn world(seed: u64) -> World {
+        World::from_random_genomes(20, seed, Config::default()).unwrap()
+    }
+
+    pub(crate) fn run(world: &mut World, ticks: u64) {
+        for _ in 0..ticks {
+            world.update();
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_checksum() {
+        let mut a = world(7);
+        let mut b = world(7);
+        run(&mut a, 300);
+        run(&mut b, 300);
+        assert_eq!(checksum(&a), checksum(&b));
+        let mut c = world(8);
+        run(&mut c, 300);
+        assert_ne!(checksum(&a), checksum(&c));
+    }
+
+    #[test]
+    fn results_do_not_depend_on_creature_order() {
+        // Seed 4 has creatures fighting from the first few ticks.
+        let mut a = world(4);
+        let mut b = world(4);
+        b.creatures.reverse();
+        run(&mut a, 1200);
+        run(&mut b, 1200);
+        assert!(a.births > 0);
+        let state = |world: &World| -> Vec<(u64, glam::Vec2, f32)> {
+            let mut state: Vec<_> = world
+                .creatures
+                .iter()
+                .map(|c| (c.id, c.position, c.energy))
+                .collect();
+            state.sort_by_key(|&(id, _, _)| id);
+            state
+        };
+        assert_eq!(state(&a), state(&b));
+        // The checksum goes through creatures in the order they are stored in.
+        a.creatures.sort_by_key(|creature| creature.id);
+        b.creatures.sort_by_key(|creature| creature.id);
+        assert_eq!(checksum(&a), checksum(&b));
+    }
+
+    #[test]
+    fn thread_count_does_not_change_checksum() {
+        let checksum_with_threads = |threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| {
+                    let mut world = world(7);
+                    run(&mut world, 600);
+                    checksum(&world)
+                })
+        };
+        assert_eq!(checksum_with_threads(1), checksum_with_threads(4));
+    }
+
+    #[test]
+    fn energy_segments_feed_from_the_resource_field() {
+        let mut config = Config::default();
+        config.world.resource_field = ResourceField::Gradient {
+            from: glam::Vec2::new(-400.0, 0.0),
+            to: glam::Vec2::new(400.0, 0.0),
+            from_intensity: 0.0,
+            to_intensity: 2.0,
+        };
+        let mut world = World::new(7, config);
+        for x in [-200.0, 200.0] {
+            world
+                .spawn(Genome::default(), glam::Vec2::new(x, 0.0))
+                .unwrap();
+        }
+        let delta_time = world.config.world.delta_time as f32;
+        let expected: Vec<f32> = world
+            .creatures
+            .iter()
+            .map(|creature| {
+                let config = &world.config;
+                let income = creature.energy_income(
+                    &config.world.resource_field,
+                    config.world.size,
+                    config.world.boundary,
+                    &config.creature,
+                );
+                let net_energy = income - creature.energy_requirement(&config.creature);
+                creature.energy + net_energy * delta_time
+            })
+            .collect();
+        world.update();
+        let energy: Vec<f32> = world.creatures.iter().map(|c| c.energy).collect();
+        assert_eq!(energy, expected);
+        assert!(energy[0] < energy[1]);
+    }
+
+    #[test]
+    fn creatures_starve_without_resources() {
+        let mut config = Config::default();
+        config.world.resource_field = ResourceField::Uniform { intensity: 0.0 };
+        let mut world = World::new(7, config);
+        world.spawn(Genome::default(), glam::Vec2::ZERO).unwrap();
+        run(&mut world, 120);
+        assert_eq!(world.creatures.len(), 1);
+        assert!(
+            world.creatures[0].energy
+                < world.creatures[0].energy_capacity(&world.config.creature) / 2.0
+        );
+        run(&mut world, 120 * 60);
+        assert!(world.creatures.is_empty());
+        assert_eq!(world.deaths.starvation, 1);
+        assert_eq!(world.deaths.total(), 1);
+    }
+
+    #[test]
+    fn spawned_bodies_are_inside_the_walls() {
+        let mut config = Config::default();
+        config.world.size = glam::Vec2::new(60.0, 60.0);
+        let world = World::from_random_genomes(20, 3, config).unwrap();
+        let size = world.config.world.size;
+        // Bodies bigger than the world can't fit however they are placed.
+        let fitting: Vec<&Creature> = world
+            .creatures
+            .iter()
+            .filter(|creature| creature.radius() <= size.min_element())
+            .collect();
+        assert!(fitting.len() >= 15);
+        for creature in fitting {
+            for segment in creature.segments.iter() {
+                let (a, b) = creature.world_segment(segment);
+                assert!(a.abs().cmple(size).all() && b.abs().cmple(size).all());
+            }
+        }
+    }
+}